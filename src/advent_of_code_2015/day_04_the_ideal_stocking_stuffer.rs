@@ -2,7 +2,25 @@
 //!
 //! [link]: https://adventofcode.com/2015/day/4
 
-use crate::Solution;
+use std::{
+    env, fs,
+    io::{self, Write as _},
+    num::NonZero,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Solution,
+    md5::{self, PrefixHasher},
+};
+
+/// The environment variable with a directory to save checkpoint files in. Long
+/// searches can be resumed from these files, and report their progress to
+/// stderr while they are saved.
+const CHECKPOINT_DIR_VAR: &str = "AOC_2015_DAY_04_CHECKPOINT_DIR";
 
 /// Solves part one.
 pub fn part_one(input: &str) -> Solution {
@@ -24,7 +42,7 @@ pub fn part_one(input: &str) -> Solution {
 
     // Despite this, brute force is fast enough for finding five zeroes in under
     // a second.
-    brute_force(input, 0x0000_0fff)
+    brute_force(input, 5)
 }
 
 /// Solves part two.
@@ -34,41 +52,416 @@ pub fn part_two(input: &str) -> Solution {
     // 8-character secret key, and started with a 3, so maybe it would take
     // about 5 minutes in the worst case. This is probably not the intended
     // solution.
-    brute_force(input, 0x0000_00ff)
+    brute_force(input, 6)
+}
+
+/// Solves the puzzle with brute force, finding a hash which starts with a
+/// number of zero hex digits.
+fn brute_force(input: &str, zero_hex_digits: u32) -> Solution {
+    let difficulty =
+        Difficulty::zero_hex_digits(zero_hex_digits).expect("difficulty should be valid");
+
+    // Searches can take a while, so they can save checkpoints and report their
+    // progress. This is opt-in so that nothing else is printed normally. Each
+    // secret key and difficulty has its own checkpoint file, which is named
+    // with the start of the secret key's hash.
+    let checkpoint_path = env::var_os(CHECKPOINT_DIR_VAR).map(|dir| {
+        let key_hash = u128::from_be_bytes(md5::compute(input.as_bytes())) >> 64;
+        let name = format!("day_04_{key_hash:016x}_{zero_hex_digits}_zero_hex_digits.txt");
+        PathBuf::from(dir).join(name)
+    });
+
+    let search = Search {
+        is_reporting: checkpoint_path.is_some(),
+        checkpoint_path: checkpoint_path.as_deref(),
+        ..Search::new(input, difficulty)
+    };
+
+    match search.run() {
+        Ok(Some(number)) => number.into(),
+        Ok(None) => Solution::SolveError,
+        Err(error) => {
+            eprintln!("Error: checkpoint file could not be used: {error}");
+            Solution::SolveError
+        }
+    }
+}
+
+/// A pattern that the start of an MD5 hash needs to match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Difficulty {
+    /// The bits of the hash which are checked, from the most significant bit.
+    mask: u128,
+
+    /// The value of the checked bits.
+    value: u128,
+}
+
+impl Difficulty {
+    /// Creates a new `Difficulty` for a hash which starts with a number of zero
+    /// hex digits. This function returns [`None`] if there are more than 32.
+    fn zero_hex_digits(count: u32) -> Option<Self> {
+        Self::zero_bits(count.checked_mul(4)?)
+    }
+
+    /// Creates a new `Difficulty` for a hash which starts with a number of zero
+    /// bits. This function returns [`None`] if there are more than 128.
+    fn zero_bits(count: u32) -> Option<Self> {
+        let mask = match count {
+            0 => 0,
+            1..=128 => u128::MAX << (128 - count),
+            _ => return None,
+        };
+
+        Some(Self { mask, value: 0 })
+    }
+
+    /// Returns this `Difficulty` with the hash's hex digits after the checked
+    /// bits also starting with a prefix. This function returns [`None`] if the
+    /// prefix is not hex digits or does not fit in the hash.
+    #[allow(dead_code, reason = "the puzzle only needs zero hex digits")]
+    fn with_prefix(self, prefix: &str) -> Option<Self> {
+        let start = self.mask.leading_ones();
+        let len = u32::try_from(prefix.len()).ok()?.checked_mul(4)?;
+
+        if start + len > 128 {
+            return None;
+        }
+
+        let mut mask = self.mask;
+        let mut value = self.value;
+
+        for (index, char) in (0..).zip(prefix.chars()) {
+            let shift = 124 - start - index * 4;
+            mask |= 0xf << shift;
+            value |= u128::from(char.to_digit(16)?) << shift;
+        }
+
+        Some(Self { mask, value })
+    }
+
+    /// Returns `true` if an MD5 hash matches this `Difficulty`.
+    fn matches(self, hash: [u8; 16]) -> bool {
+        u128::from_be_bytes(hash) & self.mask == self.value
+    }
+}
+
+/// A search for the lowest number which makes a secret key's MD5 hash match a
+/// difficulty.
+struct Search<'a> {
+    /// The secret key.
+    key: &'a str,
+
+    /// The difficulty.
+    difficulty: Difficulty,
+
+    /// The number of threads to search with.
+    thread_count: usize,
+
+    /// The number of numbers which each thread takes at a time.
+    chunk_len: u64,
+
+    /// The time between each progress report and checkpoint.
+    interval: Duration,
+
+    /// Whether progress is reported to stderr.
+    is_reporting: bool,
+
+    /// The path to a checkpoint file to resume from and save to, if any.
+    checkpoint_path: Option<&'a Path>,
+}
+
+impl<'a> Search<'a> {
+    /// Creates a new `Search` for a secret key and a difficulty on every
+    /// available thread.
+    fn new(key: &'a str, difficulty: Difficulty) -> Self {
+        Self {
+            key,
+            difficulty,
+            thread_count: thread::available_parallelism().map_or(1, NonZero::get),
+            chunk_len: 10_000,
+            interval: Duration::from_secs(10),
+            is_reporting: false,
+            checkpoint_path: None,
+        }
+    }
+
+    /// Runs the `Search` and returns the lowest number, or [`None`] if no
+    /// number is found. This function returns an error if the checkpoint file
+    /// could not be read or saved.
+    fn run(&self) -> io::Result<Option<u64>> {
+        // Resume from the checkpoint file if it exists. The numbers start from
+        // 1 otherwise.
+        let start = match self.checkpoint_path {
+            Some(path) => self.load_checkpoint(path)?.unwrap_or(1),
+            None => 1,
+        };
+
+        // The secret key is the same for every number, so it is only hashed
+        // once. Each number's digits are then written after it in the final
+        // block.
+        let key_hasher = PrefixHasher::new(self.key.as_bytes());
+
+        let progress = Mutex::new(Progress {
+            next_start: Some(start),
+            active_starts: Vec::with_capacity(self.thread_count),
+            lowest_number: None,
+            searched_count: 0,
+            running_count: self.thread_count,
+            is_cancelled: false,
+        });
+
+        let main_thread = thread::current();
+
+        let result = thread::scope(|scope| {
+            for _ in 0..self.thread_count {
+                scope.spawn(|| {
+                    self.search_chunks(key_hasher.clone(), &progress);
+                    lock(&progress).running_count -= 1;
+                    main_thread.unpark();
+                });
+            }
+
+            if self.is_reporting || self.checkpoint_path.is_some() {
+                self.monitor(&progress)
+            } else {
+                Ok(())
+            }
+        });
+
+        result.map(|()| lock(&progress).lowest_number)
+    }
+
+    /// Searches chunks of numbers on the current thread until a number is
+    /// found or the `Search` is cancelled.
+    fn search_chunks(&self, mut key_hasher: PrefixHasher, progress: &Mutex<Progress>) {
+        loop {
+            let start = {
+                let mut progress = lock(progress);
+
+                // Chunks are taken in order, so any later chunk can only have
+                // higher numbers.
+                let Some(start) = progress.next_start.filter(|&start| {
+                    !progress.is_cancelled && progress.lowest_number.is_none_or(|n| start < n)
+                }) else {
+                    break;
+                };
+
+                progress.next_start = start.checked_add(self.chunk_len);
+                progress.active_starts.push(start);
+                start
+            };
+
+            let end = start.saturating_add(self.chunk_len - 1);
+            let number = search(&mut key_hasher, self.difficulty, start, end);
+
+            let mut progress = lock(progress);
+            progress.active_starts.retain(|&s| s != start);
+            progress.searched_count += number.unwrap_or(end) - start + 1;
+
+            if let Some(number) = number {
+                progress.lowest_number =
+                    Some(progress.lowest_number.map_or(number, |n| n.min(number)));
+                break;
+            }
+        }
+    }
+
+    /// Reports progress and saves checkpoints on the current thread until
+    /// every thread has finished searching. The `Search` is cancelled if a
+    /// checkpoint could not be saved.
+    fn monitor(&self, progress: &Mutex<Progress>) -> io::Result<()> {
+        let mut time = Instant::now();
+        let mut searched_count = 0;
+
+        loop {
+            let deadline = time + self.interval;
+
+            // Wait for the next report, or for the threads to finish.
+            loop {
+                if lock(progress).running_count == 0 {
+                    return Ok(());
+                }
+
+                let now = Instant::now();
+
+                if now >= deadline {
+                    break;
+                }
+
+                thread::park_timeout(deadline - now);
+            }
+
+            let (checkpoint, next_searched_count) = {
+                let progress = lock(progress);
+                (progress.checkpoint(), progress.searched_count)
+            };
+
+            let elapsed = time.elapsed();
+            let count = next_searched_count - searched_count;
+            time = deadline;
+            searched_count = next_searched_count;
+
+            let Some(checkpoint) = checkpoint else {
+                continue;
+            };
+
+            if self.is_reporting {
+                let rate = u128::from(count) * 1000 / elapsed.as_millis().max(1);
+                eprintln!("Searched up to {checkpoint} ({rate} numbers per second)");
+            }
+
+            if let Some(path) = self.checkpoint_path
+                && let Err(error) = self.save_checkpoint(path, checkpoint)
+            {
+                lock(progress).is_cancelled = true;
+                return Err(error);
+            }
+        }
+    }
+
+    /// Returns the number to resume from in a checkpoint file, or [`None`] if
+    /// it does not exist. This function returns an error if the checkpoint
+    /// file could not be read or is for a different `Search`.
+    fn load_checkpoint(&self, path: &Path) -> io::Result<Option<u64>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        // The secret key is last because it may contain newlines.
+        let mut lines = contents.splitn(4, '\n');
+        let mut parse_line = |radix| u128::from_str_radix(lines.next()?, radix).ok();
+        let start = parse_line(10).and_then(|n| u64::try_from(n).ok());
+        let mask = parse_line(16);
+        let value = parse_line(16);
+
+        match (start, mask, value, lines.next()) {
+            (Some(start), Some(mask), Some(value), Some(key))
+                if key == self.key && Difficulty { mask, value } == self.difficulty =>
+            {
+                Ok(Some(start))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "checkpoint file is for a different search",
+            )),
+        }
+    }
+
+    /// Saves the number to resume from to a checkpoint file.
+    fn save_checkpoint(&self, path: &Path, start: u64) -> io::Result<()> {
+        let Difficulty { mask, value } = self.difficulty;
+        let contents = format!("{start}\n{mask:x}\n{value:x}\n{}", self.key);
+
+        // Replace the checkpoint file in one step so that it is never left
+        // incomplete.
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, contents)?;
+        fs::rename(temp_path, path)
+    }
 }
 
-/// Solves the puzzle with brute force.
-fn brute_force(input: &str, mask: u32) -> Solution {
-    // Keep the secret key length in a variable. This might be a meaningless
-    // micro-optimization, but speed is important here.
-    let len = input.len();
+/// The progress of a [`Search`], shared between its threads.
+struct Progress {
+    /// The first number of the next chunk, or [`None`] if there are no more
+    /// chunks.
+    next_start: Option<u64>,
+
+    /// The first numbers of the chunks being searched.
+    active_starts: Vec<u64>,
+
+    /// The lowest number found so far, if any.
+    lowest_number: Option<u64>,
 
-    // Store the secret key in a byte buffer.
-    let mut buffer = input.as_bytes().to_vec();
+    /// The number of numbers which have been searched.
+    searched_count: u64,
 
-    // The number is probably the same length as the secret key, so reserve that
-    // many bytes to avoid reallocating.
-    buffer.reserve(len);
+    /// The number of threads which are still searching.
+    running_count: usize,
 
-    // Check every number without leading zeroes.
-    for number in 1..=u32::MAX {
-        // Truncate the buffer to the length of the secret key. Don't bother
-        // emptying and refilling it.
-        buffer.truncate(len);
+    /// Whether the threads should stop searching.
+    is_cancelled: bool,
+}
 
-        buffer.extend_from_slice(number.to_string().as_bytes());
-        let hash = md5::compute(&buffer).0;
+impl Progress {
+    /// Returns the lowest number which has not been searched or has been
+    /// found, or [`None`] if every number has been searched.
+    fn checkpoint(&self) -> Option<u64> {
+        let start = self.active_starts.iter().copied().min().or(self.next_start);
 
-        if u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]) <= mask {
-            return number.into();
+        match (start, self.lowest_number) {
+            (Some(start), Some(number)) => Some(start.min(number)),
+            (start, number) => start.or(number),
         }
     }
+}
 
-    Solution::SolveError
+/// Locks a mutex, ignoring whether another thread panicked while holding it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the lowest number from a start number to an end number which makes
+/// a secret key's MD5 hash match a difficulty, using a hasher which has hashed
+/// the secret key. This function returns [`None`] if no number is found.
+fn search(
+    key_hasher: &mut PrefixHasher,
+    difficulty: Difficulty,
+    start: u64,
+    end: u64,
+) -> Option<u64> {
+    let mut counter = Counter::new(start);
+
+    for number in start..=end {
+        if difficulty.matches(key_hasher.hash(&counter.digits)) {
+            return Some(number);
+        }
+
+        counter.increment();
+    }
+
+    None
+}
+
+/// A number stored as decimal digits. The number can be incremented without
+/// reallocating the digits.
+struct Counter {
+    /// The number's decimal digits.
+    digits: Vec<u8>,
+}
+
+impl Counter {
+    /// Creates a new `Counter` from a number.
+    fn new(number: u64) -> Self {
+        // Reserve enough digits for the highest number.
+        let mut digits = Vec::with_capacity(20);
+        let _ = write!(digits, "{number}");
+        Self { digits }
+    }
+
+    /// Increments the number.
+    fn increment(&mut self) {
+        for digit in self.digits.iter_mut().rev() {
+            // Carry to the next digit if it is 9.
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                return;
+            }
+        }
+
+        // Every digit was 9, so the number has another digit.
+        self.digits.insert(0, b'1');
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{env, process};
+
     use super::*;
 
     /// Tests part one.
@@ -77,4 +470,150 @@ mod tests {
         assert_eq!(part_one("abcdef"), 609_043.into());
         assert_eq!(part_one("pqrstuv"), 1_048_970.into());
     }
+
+    /// Tests that searching on multiple threads finds the lowest number.
+    #[test]
+    fn search_works() {
+        check_search(Difficulty::zero_hex_digits(2), |hex| hex.starts_with("00"));
+        check_search(Difficulty::zero_bits(0), |_| true);
+
+        check_search(
+            Difficulty::zero_bits(3).and_then(|d| d.with_prefix("f")),
+            |hex| hex.starts_with("1e") || hex.starts_with("1f"),
+        );
+
+        check_search(
+            Difficulty::zero_hex_digits(0).and_then(|d| d.with_prefix("aB")),
+            |hex| hex.starts_with("ab"),
+        );
+
+        check_search(
+            Difficulty::zero_hex_digits(1).and_then(|d| d.with_prefix("C")),
+            |hex| hex.starts_with("0c"),
+        );
+    }
+
+    /// Tests resuming from and saving checkpoints.
+    #[test]
+    fn checkpoints_work() {
+        let path = env::temp_dir().join(format!("advent-of-code-day-04-{}.txt", process::id()));
+        let difficulty = Difficulty::zero_hex_digits(4).expect("difficulty should be valid");
+
+        let search = Search {
+            thread_count: 2,
+            chunk_len: 1000,
+            interval: Duration::from_millis(1),
+            checkpoint_path: Some(&path),
+            ..Search::new("abcdef", difficulty)
+        };
+
+        search
+            .save_checkpoint(&path, 40_000)
+            .expect("checkpoint should be saved");
+        assert_eq!(search.run().ok(), Some(Some(72_162)));
+
+        // Checkpoints are only saved for numbers before the lowest number.
+        let start = search
+            .load_checkpoint(&path)
+            .expect("checkpoint should be loaded");
+        assert!(
+            start.is_some_and(|n| (40_000..=72_162).contains(&n)),
+            "{start:?}"
+        );
+
+        let other_search = Search {
+            checkpoint_path: Some(&path),
+            ..Search::new("pqrstuv", difficulty)
+        };
+
+        let error = other_search
+            .run()
+            .expect_err("checkpoint should be for a different search");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(&path).expect("checkpoint should be removed");
+        assert_eq!(search.load_checkpoint(&path).ok(), Some(None));
+    }
+
+    /// Tests creating and matching difficulties.
+    #[test]
+    fn difficulty_works() {
+        let hash = *b"\x00\x00\x0f\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff";
+
+        for count in 0..=20 {
+            let difficulty = Difficulty::zero_bits(count).expect("difficulty should be valid");
+            assert!(difficulty.matches(hash));
+        }
+
+        assert!(
+            !Difficulty::zero_bits(21)
+                .expect("difficulty should be valid")
+                .matches(hash)
+        );
+        assert!(
+            Difficulty::zero_bits(128)
+                .expect("difficulty should be valid")
+                .matches([0; 16])
+        );
+        assert_eq!(Difficulty::zero_bits(129), None);
+        assert_eq!(Difficulty::zero_hex_digits(33), None);
+        assert_eq!(Difficulty::zero_hex_digits(u32::MAX), None);
+
+        let difficulty = Difficulty::zero_hex_digits(4).expect("difficulty should be valid");
+        assert!(
+            difficulty
+                .with_prefix("0fff")
+                .is_some_and(|d| d.matches(hash))
+        );
+        assert!(
+            difficulty
+                .with_prefix("0ffe")
+                .is_some_and(|d| !d.matches(hash))
+        );
+        assert_eq!(difficulty.with_prefix("0g"), None);
+        assert_eq!(difficulty.with_prefix(&"0".repeat(29)), None);
+        assert!(difficulty.with_prefix(&"f".repeat(28)).is_some());
+    }
+
+    /// Tests incrementing a [`Counter`].
+    #[test]
+    fn counter_works() {
+        let mut counter = Counter::new(98);
+
+        for expected_digits in [b"99".as_slice(), b"100", b"101"] {
+            counter.increment();
+            assert_eq!(counter.digits, expected_digits);
+        }
+
+        let mut counter = Counter::new(9_999_999_999_999_999_999);
+        counter.increment();
+        assert_eq!(counter.digits, b"10000000000000000000");
+        assert_eq!(counter.digits.capacity(), 20);
+    }
+
+    /// Checks that searching with any number of threads finds the lowest number
+    /// whose hash's hex digits match a function.
+    fn check_search(difficulty: Option<Difficulty>, matches: impl Fn(&str) -> bool) {
+        let difficulty = difficulty.expect("difficulty should be valid");
+
+        for key in ["abcdef", "pqrstuv", "xyz"] {
+            // Find the lowest number without chunks or threads.
+            let expected_number = (1..=u64::MAX).find(|n| {
+                let hash = md5::compute(format!("{key}{n}").as_bytes());
+                matches(&format!("{:032x}", u128::from_be_bytes(hash)))
+            });
+
+            // Use small chunks so that the threads take several of them.
+            for thread_count in 1..=4 {
+                let search = Search {
+                    thread_count,
+                    chunk_len: 16,
+                    ..Search::new(key, difficulty)
+                };
+
+                let number = search.run().expect("search should not use files");
+                assert_eq!(number, expected_number);
+            }
+        }
+    }
 }
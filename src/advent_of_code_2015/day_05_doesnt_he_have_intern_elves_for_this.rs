@@ -2,84 +2,196 @@
 //!
 //! [link]: https://adventofcode.com/2015/day/5
 
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
 use crate::Solution;
 
+/// The rules for nice strings in part one.
+const PART_ONE_RULES: &str = "at least 3 of aeiou; repeat gap 0; forbid ab cd pq xy";
+
+/// The rules for nice strings in part two.
+const PART_TWO_RULES: &str = "repeated pair; repeat gap 1";
+
 /// Solves part one.
 pub fn part_one(input: &str) -> Solution {
     // Santa wants to know how many strings in his text file are nice.
-    solve_part(input, is_string_nice_part_one)
+    solve_part(input, PART_ONE_RULES)
 }
 
 /// Solves part two.
 pub fn part_two(input: &str) -> Solution {
     // Now he has changed his rules for nice strings.
-    solve_part(input, is_string_nice_part_two)
+    solve_part(input, PART_TWO_RULES)
 }
 
-/// Solves a part with a predicate for whether a string is nice.
-fn solve_part(input: &str, is_string_nice: fn(string: &str) -> bool) -> Solution {
-    input.lines().filter(|&l| is_string_nice(l)).count().into()
+/// Solves a part with the rules for nice strings.
+fn solve_part(input: &str, rules: &str) -> Solution {
+    let rules = RuleSet::parse(rules).expect("rules should be valid");
+    input.lines().filter(|&l| rules.matches(l)).count().into()
 }
 
-/// Returns `true` if a string is nice for part one.
-fn is_string_nice_part_one(string: &str) -> bool {
-    // Nice strings contain at least three vowels.
-    let mut vowel_count = 0;
-
-    // Nice strings contain at least one letter that appears twice in a row.
-    let mut previous_letter = '\0';
-    let mut has_double = false;
-
-    // Nice strings must not contain the pairs "ab", "cd", "pq", or "xy".
-    for letter in string.chars() {
-        match (previous_letter, letter) {
-            (_, 'a' | 'e' | 'i' | 'o' | 'u') => vowel_count += 1,
-            ('a', 'b') | ('c', 'd') | ('p', 'q') | ('x', 'y') => return false,
-            _ => (),
-        }
+/// A set of rules which a string needs to follow.
+#[derive(Debug, PartialEq, Eq)]
+struct RuleSet {
+    /// The rules.
+    rules: Vec<Rule>,
+}
 
-        if letter == previous_letter {
-            has_double = true;
-        }
+impl RuleSet {
+    /// Parses a new `RuleSet` from rules separated by semicolons or newlines.
+    fn parse(source: &str) -> Result<Self, RuleError> {
+        let rules = source
+            .split([';', '\n'])
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .map(|r| Rule::parse(r).ok_or_else(|| RuleError { rule: r.to_owned() }))
+            .collect::<Result<_, _>>()?;
 
-        previous_letter = letter;
+        Ok(Self { rules })
     }
 
-    has_double && vowel_count >= 3
+    /// Returns `true` if a string follows every rule in the `RuleSet`.
+    fn matches(&self, string: &str) -> bool {
+        // Convert the string to fixed-size characters. This allows sliding
+        // windows and faster indexing.
+        let chars: Box<[char]> = string.chars().collect();
+        self.rules.iter().all(|r| r.matches(string, &chars))
+    }
+}
+
+/// A rule which a string needs to follow.
+#[derive(Debug, PartialEq, Eq)]
+enum Rule {
+    /// The string contains at least a number of characters from a class.
+    AtLeast(usize, CharClass),
+
+    /// The string contains a character repeated with a number of characters
+    /// between them.
+    Repeat(usize),
+
+    /// The string contains a pair of characters at least twice without
+    /// overlapping.
+    RepeatedPair,
+
+    /// The string does not contain any substrings.
+    Forbid(Vec<String>),
 }
 
-/// Returns `true` if a string is nice for part two.
-fn is_string_nice_part_two(string: &str) -> bool {
-    // Convert the string to fixed-size characters. This allows sliding windows
-    // and faster indexing.
-    let string: Box<[char]> = string.chars().collect();
-
-    // A string is nice if any pair of letters appears at least twice. Overlaps
-    // of the same three letters don't count.
-    let mut has_matching_pairs = false;
-
-    'check_pairs: for (index, pair) in string.windows(2).map(|w| (w[0], w[1])).enumerate() {
-        for other_pair in string[index + 2..].windows(2).map(|w| (w[0], w[1])) {
-            if pair == other_pair {
-                has_matching_pairs = true;
-                break 'check_pairs;
+impl Rule {
+    /// Parses a new `Rule` from a string. This function returns [`None`] if the
+    /// string is not a valid rule.
+    fn parse(source: &str) -> Option<Self> {
+        let words: Vec<&str> = source.split_whitespace().collect();
+
+        let rule = match words[..] {
+            ["at", "least", count, "of", class] => {
+                Self::AtLeast(count.parse().ok()?, CharClass::parse(class)?)
             }
-        }
+            ["repeat", "gap", gap] => {
+                // The gap and the two repeated characters need to fit in a
+                // window.
+                let gap: usize = gap.parse().ok()?;
+                gap.checked_add(2)?;
+                Self::Repeat(gap)
+            }
+            ["repeated", "pair"] => Self::RepeatedPair,
+            ["forbid", ref substrings @ ..] if !substrings.is_empty() => {
+                Self::Forbid(substrings.iter().map(|&s| s.to_owned()).collect())
+            }
+            _ => return None,
+        };
+
+        Some(rule)
     }
 
-    if !has_matching_pairs {
-        return false;
+    /// Returns `true` if a string and its characters follow the `Rule`.
+    fn matches(&self, string: &str, chars: &[char]) -> bool {
+        match self {
+            Self::AtLeast(count, class) => {
+                chars
+                    .iter()
+                    .filter(|&&c| class.contains(c))
+                    .take(*count)
+                    .count()
+                    == *count
+            }
+            Self::Repeat(gap) => chars.windows(gap + 2).any(|w| w[0] == w[gap + 1]),
+            Self::RepeatedPair => {
+                // Pairs only repeat without overlapping if they are at least
+                // two characters after their first appearance.
+                let mut first_indices = HashMap::new();
+
+                chars.windows(2).enumerate().any(|(index, pair)| {
+                    let first_index = *first_indices.entry((pair[0], pair[1])).or_insert(index);
+                    index >= first_index + 2
+                })
+            }
+            Self::Forbid(substrings) => !substrings.iter().any(|s| string.contains(s.as_str())),
+        }
     }
+}
 
-    // Nice strings must also have a repeated letter with any letter between
-    // them.
-    for window in string.windows(3) {
-        if window[0] == window[2] {
-            return true;
+/// A class of characters made of single characters and ranges.
+#[derive(Debug, PartialEq, Eq)]
+struct CharClass {
+    /// The inclusive ranges of characters in the class.
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    /// Parses a new `CharClass` from characters and ranges such as `a-z`. This
+    /// function returns [`None`] if the class is empty or contains an inverted
+    /// range.
+    fn parse(source: &str) -> Option<Self> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut ranges = Vec::new();
+        let mut index = 0;
+
+        while index < chars.len() {
+            let range = match chars[index..] {
+                [start, '-', end, ..] => {
+                    index += 3;
+                    (start, end)
+                }
+                [char, ..] => {
+                    index += 1;
+                    (char, char)
+                }
+                [] => unreachable!("index should be in bounds"),
+            };
+
+            if range.0 > range.1 {
+                return None;
+            }
+
+            ranges.push(range);
         }
+
+        (!ranges.is_empty()).then_some(Self { ranges })
     }
 
-    false
+    /// Returns `true` if the `CharClass` contains a character.
+    fn contains(&self, char: char) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&char))
+    }
+}
+
+/// An error caused by a rule which could not be parsed.
+#[derive(Debug, PartialEq, Eq)]
+struct RuleError {
+    /// The rule.
+    rule: String,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid rule {:?}", self.rule)
+    }
 }
 
 #[cfg(test)]
@@ -89,19 +201,75 @@ mod tests {
     /// Tests part one.
     #[test]
     fn part_one_works() {
-        assert!(is_string_nice_part_one("ugknbfddgicrmopn"));
-        assert!(is_string_nice_part_one("aaa"));
-        assert!(!is_string_nice_part_one("jchzalrnumimnmhp"));
-        assert!(!is_string_nice_part_one("haegwjzuvuyypxyu"));
-        assert!(!is_string_nice_part_one("dvszwmarrgswjxmb"));
+        let rules = RuleSet::parse(PART_ONE_RULES).expect("rules should be valid");
+        assert!(rules.matches("ugknbfddgicrmopn"));
+        assert!(rules.matches("aaa"));
+        assert!(!rules.matches("jchzalrnumimnmhp"));
+        assert!(!rules.matches("haegwjzuvuyypxyu"));
+        assert!(!rules.matches("dvszwmarrgswjxmb"));
     }
 
     /// Tests part two.
     #[test]
     fn part_two_works() {
-        assert!(is_string_nice_part_two("qjhvhtzxzqqjkmpb"));
-        assert!(is_string_nice_part_two("xxyxx"));
-        assert!(!is_string_nice_part_two("uurcxstgmygtbstg"));
-        assert!(!is_string_nice_part_two("ieodomkazucvgmuy"));
+        let rules = RuleSet::parse(PART_TWO_RULES).expect("rules should be valid");
+        assert!(rules.matches("qjhvhtzxzqqjkmpb"));
+        assert!(rules.matches("xxyxx"));
+        assert!(!rules.matches("uurcxstgmygtbstg"));
+        assert!(!rules.matches("ieodomkazucvgmuy"));
+    }
+
+    /// Tests parsing and matching custom rules.
+    #[test]
+    fn rules_work() {
+        let rules = RuleSet::parse("at least 2 of 0-9A-F\n\nrepeat gap 2 ;forbid  zz")
+            .expect("rules should be valid");
+
+        assert_eq!(
+            rules.rules,
+            [
+                Rule::AtLeast(
+                    2,
+                    CharClass {
+                        ranges: vec![('0', '9'), ('A', 'F')]
+                    }
+                ),
+                Rule::Repeat(2),
+                Rule::Forbid(vec!["zz".to_owned()]),
+            ]
+        );
+
+        assert!(rules.matches("1xy1"));
+        assert!(rules.matches("BxyB"));
+        assert!(!rules.matches("Bxyb"));
+        assert!(!rules.matches("axyaB"));
+        assert!(!rules.matches("1zz1"));
+
+        let rules =
+            RuleSet::parse("at least 0 of -; repeated pair").expect("rules should be valid");
+        assert!(rules.matches("abab"));
+        assert!(rules.matches("aaaa"));
+        assert!(!rules.matches("aaa"));
+
+        let rules = RuleSet::parse(&format!("repeat gap {}", usize::MAX - 2))
+            .expect("rules should be valid");
+        assert!(!rules.matches("aaaa"));
+
+        assert_eq!(RuleSet::parse(""), Ok(RuleSet { rules: Vec::new() }));
+        assert!(RuleSet::parse("").is_ok_and(|r| r.matches("anything")));
+
+        for rule in [
+            "forbid",
+            "at least x of a",
+            "at least 1 of z-a",
+            "repeat gap -1",
+            "repeat gap 18446744073709551615",
+            "be nice",
+        ] {
+            let error = RuleError {
+                rule: rule.to_owned(),
+            };
+            assert_eq!(RuleSet::parse(&format!("repeat gap 0; {rule}")), Err(error));
+        }
     }
 }
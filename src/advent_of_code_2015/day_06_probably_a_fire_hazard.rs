@@ -2,102 +2,383 @@
 //!
 //! [link]: https://adventofcode.com/2015/day/6
 
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+};
+
 use crate::Solution;
 
-/// A function for applying an [`Action`] to a slice of lights.
-type Adjuster = fn(action: Action, lights: &mut [u16]);
+/// The width and height of Santa's grid of lights.
+const GRID_SIZE: u32 = 1000;
 
 /// Solves part one.
 pub fn part_one(input: &str) -> Solution {
-    solve_part(input, adjust_lights_part_one)
+    solve_part(input, &PartOneModel)
 }
 
 /// Solves part two.
 pub fn part_two(input: &str) -> Solution {
-    solve_part(input, adjust_lights_part_two)
+    solve_part(input, &PartTwoModel)
 }
 
-/// Solves a part with an [`Adjuster`].
-fn solve_part(input: &str, adjuster: Adjuster) -> Solution {
-    let mut grid = Grid::new();
+/// Solves a part with a [`LightModel`].
+fn solve_part(input: &str, model: &impl LightModel) -> Solution {
+    let mut grid = Grid::new(GRID_SIZE, GRID_SIZE);
 
     for instruction in input.lines() {
-        let Some((action, rect)) = parse_instruction(instruction) else {
+        let Ok((action, rect)) = parse_instruction(instruction, GRID_SIZE, GRID_SIZE, model) else {
             return Solution::ParseError;
         };
 
-        grid.apply_action(action, rect, adjuster);
+        grid.apply_action(action, rect, model);
     }
 
-    grid.brightness().into()
+    grid.brightness(model).into()
 }
 
-/// Applies an [`Action`] to a slice of lights for part one.
-fn adjust_lights_part_one(action: Action, lights: &mut [u16]) {
-    match action {
-        Action::TurnOn => lights.fill(1),
-        Action::TurnOff => lights.fill(0),
-        Action::Toggle => {
-            for light in lights {
-                *light = u16::from(*light == 0);
+/// A model of how lights respond to [`Action`]s.
+trait LightModel {
+    /// The state of a light.
+    type Light: Copy + Default;
+
+    /// Applies an [`Action`] to a slice of lights.
+    fn adjust_lights(&self, action: Action, lights: &mut [Self::Light]);
+
+    /// Returns the brightness of a light.
+    fn brightness(&self, light: Self::Light) -> u64;
+
+    /// Returns the code of the [`Action::Custom`] for an instruction verb
+    /// other than "turn on", "turn off", or "toggle". This function returns
+    /// [`None`] if the `LightModel` doesn't understand the verb.
+    fn parse_extra_verb(&self, _verb: &str) -> Option<u8> {
+        None
+    }
+}
+
+/// The [`LightModel`] for part one, where lights are either on or off.
+struct PartOneModel;
+
+impl LightModel for PartOneModel {
+    type Light = u16;
+
+    fn adjust_lights(&self, action: Action, lights: &mut [u16]) {
+        match action {
+            Action::TurnOn => lights.fill(1),
+            Action::TurnOff => lights.fill(0),
+            Action::Toggle => {
+                for light in lights {
+                    *light = u16::from(*light == 0);
+                }
             }
+            Action::Custom(_) => (),
         }
     }
+
+    fn brightness(&self, light: u16) -> u64 {
+        u64::from(light)
+    }
 }
 
-/// Applies an [`Action`] to a slice of lights for part two.
-fn adjust_lights_part_two(action: Action, lights: &mut [u16]) {
-    match action {
-        Action::TurnOn => {
-            for light in lights {
-                *light += 1;
+/// The [`LightModel`] for part two, where lights have a brightness.
+struct PartTwoModel;
+
+impl LightModel for PartTwoModel {
+    type Light = u16;
+
+    fn adjust_lights(&self, action: Action, lights: &mut [u16]) {
+        match action {
+            Action::TurnOn => {
+                for light in lights {
+                    *light += 1;
+                }
             }
-        }
-        Action::TurnOff => {
-            for light in lights {
-                *light = light.saturating_sub(1);
+            Action::TurnOff => {
+                for light in lights {
+                    *light = light.saturating_sub(1);
+                }
             }
-        }
-        Action::Toggle => {
-            for light in lights {
-                *light += 2;
+            Action::Toggle => {
+                for light in lights {
+                    *light += 2;
+                }
             }
+            Action::Custom(_) => (),
         }
     }
+
+    fn brightness(&self, light: u16) -> u64 {
+        u64::from(light)
+    }
 }
 
 /// A grid of lights.
-struct Grid {
-    /// The brightness of each light.
-    lights: Box<[u16]>,
+struct Grid<L> {
+    /// The number of lights in each row.
+    width: usize,
+
+    /// The state of each light.
+    lights: Box<[L]>,
 }
 
-impl Grid {
-    /// Creates a new `Grid`.
-    fn new() -> Self {
+impl<L: Copy + Default> Grid<L> {
+    /// Creates a new `Grid` from its width and height.
+    fn new(width: u32, height: u32) -> Self {
+        let width = usize::try_from(width).expect("width should fit in usize");
+        let height = usize::try_from(height).expect("height should fit in usize");
+
         Self {
-            lights: vec![0; 1_000_000].into_boxed_slice(),
+            width,
+            lights: vec![L::default(); width * height].into_boxed_slice(),
         }
     }
 
-    /// Applies an [`Action`] to a [`Rect`] of the grid with an [`Adjuster`].
-    fn apply_action(&mut self, action: Action, rect: Rect, adjuster: Adjuster) {
-        let left = usize::from(rect.left);
-        let right = usize::from(rect.right);
+    /// Applies an [`Action`] to a [`Rect`] of the grid with a [`LightModel`].
+    /// The [`Rect`] must be inside of the grid.
+    fn apply_action(&mut self, action: Action, rect: Rect, model: &impl LightModel<Light = L>) {
+        let left = usize::try_from(rect.left).expect("rect should fit in usize");
+        let right = usize::try_from(rect.right).expect("rect should fit in usize");
 
         for y in rect.top..=rect.bottom {
-            let index = usize::from(y) * 1000;
+            let index = usize::try_from(y).expect("rect should fit in usize") * self.width;
             let slice = &mut self.lights[(index + left)..=(index + right)];
-            adjuster(action, slice);
+            model.adjust_lights(action, slice);
         }
     }
 
-    /// Returns the total brightness of the grid.
-    fn brightness(&self) -> u32 {
+    /// Returns the total brightness of the grid with a [`LightModel`].
+    fn brightness(&self, model: &impl LightModel<Light = L>) -> u64 {
         let mut brightness = 0;
 
         for light in &self.lights {
-            brightness += u32::from(*light);
+            brightness += model.brightness(*light);
+        }
+
+        brightness
+    }
+
+    /// Writes the grid as a binary PGM image with a [`LightModel`]. The
+    /// brightness of each light is scaled so that the brightest light is white,
+    /// so lights which are only on or off are black and white.
+    #[allow(dead_code, reason = "grids are only drawn for debugging")]
+    fn write_pgm(
+        &self,
+        model: &impl LightModel<Light = L>,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        let brightnesses = self.lights.iter().map(|&l| model.brightness(l));
+        let max_brightness = brightnesses.clone().max().unwrap_or(0).max(1);
+
+        let pixels: Vec<u8> = brightnesses
+            .map(|b| {
+                let pixel = u128::from(b) * 255 / u128::from(max_brightness);
+                pixel.try_into().expect("pixel should be less than 256")
+            })
+            .collect();
+
+        let height = self.lights.len() / self.width;
+        write!(writer, "P5\n{} {height}\n255\n", self.width)?;
+        writer.write_all(&pixels)
+    }
+}
+
+/// Writes a sequence of binary PGM images of a [`Grid`] with a width and height
+/// after each [`Action`] is applied to it with a [`LightModel`]. Each image is
+/// scaled separately, so the brightest light in each image is white.
+#[allow(dead_code, reason = "grids are only drawn for debugging")]
+fn write_pgm_frames(
+    instructions: &[(Action, Rect)],
+    width: u32,
+    height: u32,
+    model: &impl LightModel,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let mut grid = Grid::new(width, height);
+
+    for (action, rect) in instructions.iter().copied() {
+        grid.apply_action(action, rect, model);
+        grid.write_pgm(model, writer)?;
+    }
+
+    Ok(())
+}
+
+/// A [`Grid`] which records every [`Action`] applied to it, so that actions can
+/// be undone and the history of each light can be queried.
+#[allow(dead_code, reason = "histories are only recorded for debugging")]
+struct RecordedGrid<L> {
+    /// The [`Grid`].
+    grid: Grid<L>,
+
+    /// Each [`Action`] and [`Rect`] in the order they were applied.
+    instructions: Vec<(Action, Rect)>,
+}
+
+#[allow(dead_code, reason = "histories are only recorded for debugging")]
+impl<L: Copy + Default + PartialEq> RecordedGrid<L> {
+    /// Creates a new `RecordedGrid` from its width and height.
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            grid: Grid::new(width, height),
+            instructions: Vec::new(),
+        }
+    }
+
+    /// Applies an [`Action`] to a [`Rect`] of the grid with a [`LightModel`]
+    /// and records it. The [`Rect`] must be inside of the grid.
+    fn apply_action(&mut self, action: Action, rect: Rect, model: &impl LightModel<Light = L>) {
+        self.grid.apply_action(action, rect, model);
+        self.instructions.push((action, rect));
+    }
+
+    /// Undoes the last [`Action`] applied to the grid with a [`LightModel`]
+    /// and returns it. Actions can't always be reversed, so the grid is rebuilt
+    /// from the remaining instructions. This function returns [`None`] if no
+    /// actions have been applied.
+    fn undo(&mut self, model: &impl LightModel<Light = L>) -> Option<(Action, Rect)> {
+        let instruction = self.instructions.pop()?;
+        self.grid.lights.fill(L::default());
+
+        for (action, rect) in self.instructions.iter().copied() {
+            self.grid.apply_action(action, rect, model);
+        }
+
+        Some(instruction)
+    }
+
+    /// Returns the state of the light at a position after the instruction at
+    /// an index is applied with a [`LightModel`]. This function returns
+    /// [`None`] if there is no instruction at the index.
+    fn light_after(
+        &self,
+        x: u32,
+        y: u32,
+        index: usize,
+        model: &impl LightModel<Light = L>,
+    ) -> Option<L> {
+        if index >= self.instructions.len() {
+            return None;
+        }
+
+        let history = self.light_history(x, y, index + 1, model);
+        Some(history.last().map_or_else(L::default, |h| h.1))
+    }
+
+    /// Returns the index of the last instruction which changed the state of
+    /// the light at a position with a [`LightModel`]. This function returns
+    /// [`None`] if no instruction has changed the light.
+    fn last_change(&self, x: u32, y: u32, model: &impl LightModel<Light = L>) -> Option<usize> {
+        let history = self.light_history(x, y, self.instructions.len(), model);
+        history.last().map(|h| h.0)
+    }
+
+    /// Returns the index and new state of each instruction which changed the
+    /// light at a position with a [`LightModel`], considering only the first
+    /// `count` instructions.
+    fn light_history(
+        &self,
+        x: u32,
+        y: u32,
+        count: usize,
+        model: &impl LightModel<Light = L>,
+    ) -> Vec<(usize, L)> {
+        let mut light = [L::default()];
+        let mut history = Vec::new();
+
+        for (index, (action, rect)) in self.instructions[..count].iter().enumerate() {
+            if rect.contains(x, y) {
+                let previous = light[0];
+                model.adjust_lights(*action, &mut light);
+
+                if light[0] != previous {
+                    history.push((index, light[0]));
+                }
+            }
+        }
+
+        history
+    }
+}
+
+/// A grid of lights which is divided into regions that are always adjusted
+/// together. The regions are found from the edges of every [`Rect`] which will
+/// be adjusted, so the work done scales with the number of regions instead of
+/// the number of lights.
+#[allow(dead_code, reason = "the puzzle's grid is small enough to store")]
+struct CompressedGrid<L> {
+    /// The left edge of each column of regions, followed by the right edge of
+    /// the last column.
+    column_edges: Box<[u64]>,
+
+    /// The top edge of each row of regions, followed by the bottom edge of the
+    /// last row.
+    row_edges: Box<[u64]>,
+
+    /// The state of the lights in each region.
+    regions: Box<[L]>,
+}
+
+#[allow(dead_code, reason = "the puzzle's grid is small enough to store")]
+impl<L: Copy + Default> CompressedGrid<L> {
+    /// Creates a new `CompressedGrid` from the [`Rect`]s which will be
+    /// adjusted.
+    fn new(rects: impl IntoIterator<Item = Rect>) -> Self {
+        let mut column_edges = Vec::new();
+        let mut row_edges = Vec::new();
+
+        for rect in rects {
+            column_edges.extend([u64::from(rect.left), u64::from(rect.right) + 1]);
+            row_edges.extend([u64::from(rect.top), u64::from(rect.bottom) + 1]);
+        }
+
+        for edges in [&mut column_edges, &mut row_edges] {
+            edges.sort_unstable();
+            edges.dedup();
+        }
+
+        let region_count = column_edges.len().saturating_sub(1) * row_edges.len().saturating_sub(1);
+
+        Self {
+            column_edges: column_edges.into_boxed_slice(),
+            row_edges: row_edges.into_boxed_slice(),
+            regions: vec![L::default(); region_count].into_boxed_slice(),
+        }
+    }
+
+    /// Applies an [`Action`] to a [`Rect`] of the grid with a [`LightModel`].
+    /// The [`Rect`] must have been used to create the `CompressedGrid`.
+    fn apply_action(&mut self, action: Action, rect: Rect, model: &impl LightModel<Light = L>) {
+        let find_edge = |edges: &[u64], edge| {
+            edges
+                .binary_search(&edge)
+                .expect("rect should be aligned to the grid's regions")
+        };
+
+        let left = find_edge(&self.column_edges, u64::from(rect.left));
+        let right = find_edge(&self.column_edges, u64::from(rect.right) + 1);
+        let top = find_edge(&self.row_edges, u64::from(rect.top));
+        let bottom = find_edge(&self.row_edges, u64::from(rect.bottom) + 1);
+        let column_count = self.column_edges.len() - 1;
+
+        for row in top..bottom {
+            let index = row * column_count;
+            model.adjust_lights(action, &mut self.regions[(index + left)..(index + right)]);
+        }
+    }
+
+    /// Returns the total brightness of the grid with a [`LightModel`].
+    fn brightness(&self, model: &impl LightModel<Light = L>) -> u64 {
+        let mut brightness = 0;
+        let column_widths: Vec<_> = self.column_edges.windows(2).map(|w| w[1] - w[0]).collect();
+
+        for (row, heights) in self.row_edges.windows(2).enumerate() {
+            let height = heights[1] - heights[0];
+            let index = row * column_widths.len();
+            let regions = &self.regions[index..index + column_widths.len()];
+
+            for (region, width) in regions.iter().zip(&column_widths) {
+                brightness += model.brightness(*region) * width * height;
+            }
         }
 
         brightness
@@ -115,43 +396,117 @@ enum Action {
 
     /// Toggle the lights.
     Toggle,
+
+    /// A custom action defined by a [`LightModel`].
+    #[allow(dead_code, reason = "the puzzle's light models have no custom actions")]
+    Custom(u8),
 }
 
 /// A rectangular area of lights.
 #[derive(Clone, Copy)]
 struct Rect {
     /// The position of the leftmost light.
-    left: u16,
+    left: u32,
 
     /// The position of the rightmost light.
-    right: u16,
+    right: u32,
 
     /// The position of the topmost light.
-    top: u16,
+    top: u32,
 
     /// The position of the bottommost light.
-    bottom: u16,
+    bottom: u32,
+}
+
+#[allow(dead_code, reason = "histories are only recorded for debugging")]
+impl Rect {
+    /// Returns `true` if the `Rect` contains a position.
+    fn contains(self, x: u32, y: u32) -> bool {
+        (self.left..=self.right).contains(&x) && (self.top..=self.bottom).contains(&y)
+    }
+}
+
+/// An error caused by an instruction which could not be parsed.
+#[derive(Debug, PartialEq, Eq)]
+struct InstructionError<'a> {
+    /// The instruction.
+    instruction: &'a str,
+
+    /// The [`InstructionErrorKind`].
+    kind: InstructionErrorKind,
+}
+
+impl Display for InstructionError<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            InstructionErrorKind::Invalid => "invalid instruction",
+            InstructionErrorKind::OutOfRange => "rectangle is outside of the grid",
+            InstructionErrorKind::Inverted => "rectangle is inverted",
+        };
+
+        write!(f, "{message}: \"{}\"", self.instruction)
+    }
+}
+
+/// A kind of [`InstructionError`].
+#[derive(Debug, PartialEq, Eq)]
+enum InstructionErrorKind {
+    /// The instruction is not in the expected format.
+    Invalid,
+
+    /// The instruction's [`Rect`] is outside of the grid.
+    OutOfRange,
+
+    /// The instruction's [`Rect`] has its corners in the wrong order.
+    Inverted,
 }
 
-/// Parses an [`Action`] and a [`Rect`] from an instruction. This function
-/// returns [`None`] if an instruction could not be parsed.
-fn parse_instruction(instruction: &str) -> Option<(Action, Rect)> {
-    let mut words = instruction.split(' ');
+/// Parses an [`Action`] and a [`Rect`] from an instruction for a grid with a
+/// width, height, and [`LightModel`]. This function returns an
+/// [`InstructionError`] if an instruction could not be parsed or its [`Rect`]
+/// is not inside of the grid.
+fn parse_instruction<'a>(
+    instruction: &'a str,
+    width: u32,
+    height: u32,
+    model: &impl LightModel,
+) -> Result<(Action, Rect), InstructionError<'a>> {
+    let error = |kind| InstructionError { instruction, kind };
+
+    let (action, rect) =
+        parse_words(instruction, model).ok_or(error(InstructionErrorKind::Invalid))?;
+
+    if rect.left > rect.right || rect.top > rect.bottom {
+        Err(error(InstructionErrorKind::Inverted))
+    } else if rect.right >= width || rect.bottom >= height {
+        Err(error(InstructionErrorKind::OutOfRange))
+    } else {
+        Ok((action, rect))
+    }
+}
+
+/// Parses an [`Action`] and a [`Rect`] from the words of an instruction with a
+/// [`LightModel`]. This function returns [`None`] if an instruction could not
+/// be parsed.
+fn parse_words(instruction: &str, model: &impl LightModel) -> Option<(Action, Rect)> {
+    // The verb may have any number of words, so parse the instruction from the
+    // end.
+    let mut words = instruction.rsplitn(4, ' ');
+    let (right, bottom) = parse_position(words.next()?)?;
+
+    if words.next()? != "through" {
+        return None;
+    }
+
+    let (left, top) = parse_position(words.next()?)?;
 
     let action = match words.next()? {
-        "turn" => match words.next()? {
-            "on" => Action::TurnOn,
-            "off" => Action::TurnOff,
-            _ => return None,
-        },
+        "turn on" => Action::TurnOn,
+        "turn off" => Action::TurnOff,
         "toggle" => Action::Toggle,
-        _ => return None,
+        verb => Action::Custom(model.parse_extra_verb(verb)?),
     };
 
-    let (left, top) = parse_position(words.next()?)?;
-    words.next(); // Skip "through".
-    let (right, bottom) = parse_position(words.next()?)?;
-
     Some((
         action,
         Rect {
@@ -165,7 +520,7 @@ fn parse_instruction(instruction: &str) -> Option<(Action, Rect)> {
 
 /// Parses a light position from a word. This function returns [`None`] if a
 /// position could not be parsed.
-fn parse_position(word: &str) -> Option<(u16, u16)> {
+fn parse_position(word: &str) -> Option<(u32, u32)> {
     let mut numbers = word.split(',');
     let x = numbers.next()?.parse().ok()?;
     let y = numbers.next()?.parse().ok()?;
@@ -179,7 +534,7 @@ mod tests {
     /// Tests part one.
     #[test]
     fn part_one_works() {
-        let mut grid = TestGrid::new(adjust_lights_part_one);
+        let mut grid = TestGrid::new(PartOneModel);
         grid.check_instruction("turn on 0,0 through 999,999", 1_000_000);
         grid.check_instruction("turn off 5,0 through 9,1", 999_990);
         grid.check_instruction("toggle 0,0 through 999,0", 999_000);
@@ -189,37 +544,342 @@ mod tests {
     /// Tests part two.
     #[test]
     fn part_two_works() {
-        let mut grid = TestGrid::new(adjust_lights_part_two);
+        let mut grid = TestGrid::new(PartTwoModel);
         grid.check_instruction("turn on 0,0 through 0,0", 1);
         grid.check_instruction("toggle 0,0 through 999,999", 2_000_001);
         grid.check_instruction("turn off 0,0 through 999,999", 1_000_001);
     }
 
+    /// Tests that a [`CompressedGrid`] matches a [`Grid`].
+    #[test]
+    fn compressed_grid_works() {
+        let instructions = parse_instructions(
+            &[
+                "turn on 0,0 through 999,999",
+                "turn off 5,0 through 9,1",
+                "toggle 0,0 through 999,0",
+                "turn off 499,499 through 500,500",
+                "toggle 250,100 through 750,900",
+                "turn on 0,0 through 0,0",
+                "turn off 600,600 through 999,999",
+                "toggle 1,2 through 3,4",
+            ],
+            GRID_SIZE,
+        );
+
+        check_compressed_grid(&instructions, &PartOneModel);
+        check_compressed_grid(&instructions, &PartTwoModel);
+        check_compressed_grid(&instructions, &CappedModel { cap: 3 });
+    }
+
+    /// Tests a [`CompressedGrid`] with more lights than a [`Grid`] can store.
+    #[test]
+    fn compressed_grid_is_scalable() {
+        let instructions = parse_instructions(
+            &[
+                "turn on 0,0 through 999999,999999",
+                "toggle 0,0 through 999999,0",
+                "turn off 500000,500000 through 500001,500001",
+            ],
+            1_000_000,
+        );
+
+        let mut grid = CompressedGrid::new(instructions.iter().map(|i| i.1));
+
+        for (action, rect) in instructions {
+            grid.apply_action(action, rect, &PartOneModel);
+        }
+
+        assert_eq!(grid.brightness(&PartOneModel), 999_998_999_996);
+    }
+
+    /// Tests grids with different sizes.
+    #[test]
+    fn grid_sizes_work() {
+        let mut grid = Grid::new(3, 2);
+
+        for instruction in [
+            "turn on 0,0 through 2,1",
+            "toggle 1,0 through 1,1",
+            "turn off 2,1 through 2,1",
+        ] {
+            let (action, rect) = parse_instruction(instruction, 3, 2, &PartOneModel)
+                .expect("instruction should be valid");
+
+            grid.apply_action(action, rect, &PartOneModel);
+        }
+
+        assert_eq!(&*grid.lights, [1, 0, 1, 1, 0, 0]);
+        assert_eq!(grid.brightness(&PartOneModel), 3);
+    }
+
+    /// Tests a [`LightModel`] with a brightness cap and an extra verb.
+    #[test]
+    fn capped_model_works() {
+        let mut grid = TestGrid::new(CappedModel { cap: 3 });
+        grid.check_instruction("toggle 0,0 through 999,999", 2_000_000);
+        grid.check_instruction("toggle 0,0 through 999,0", 2_001_000);
+        grid.check_instruction("turn on 0,0 through 0,0", 2_001_000);
+        grid.check_instruction("reset 0,0 through 999,1", 1_996_000);
+        grid.check_instruction("turn off 0,2 through 999,999", 998_000);
+
+        let error = parse_instruction("reset 0,0 through 1,1", GRID_SIZE, GRID_SIZE, &PartTwoModel);
+        assert!(error.is_err());
+    }
+
+    /// Tests a [`LightModel`] with coloured lights and multi-word verbs.
+    #[test]
+    fn colour_model_works() {
+        let mut grid = TestGrid::new(ColourModel);
+        grid.check_instruction("paint red 0,0 through 9,9", 100);
+        grid.check_instruction("paint green 5,5 through 14,14", 200);
+        grid.check_instruction("turn on 0,0 through 0,0", 202);
+        grid.check_instruction("toggle 0,0 through 1,0", 200);
+        grid.check_instruction("turn off 0,0 through 999,999", 0);
+
+        assert!(
+            parse_instruction("paint 0,0 through 1,1", GRID_SIZE, GRID_SIZE, &ColourModel).is_err()
+        );
+    }
+
+    /// Tests recording and undoing instructions.
+    #[test]
+    fn recorded_grid_works() {
+        let instructions = parse_instructions(
+            &[
+                "turn on 0,0 through 2,1",
+                "toggle 1,0 through 1,1",
+                "turn off 0,0 through 0,0",
+                "turn on 1,1 through 2,1",
+            ],
+            3,
+        );
+
+        let mut grid = RecordedGrid::new(3, 3);
+
+        for (action, rect) in instructions.iter().copied() {
+            grid.apply_action(action, rect, &PartTwoModel);
+        }
+
+        assert_eq!(grid.light_after(0, 0, 1, &PartOneModel), Some(1));
+        assert_eq!(grid.light_after(0, 0, 2, &PartOneModel), Some(0));
+        assert_eq!(grid.light_after(1, 1, 1, &PartTwoModel), Some(3));
+        assert_eq!(grid.light_after(0, 2, 3, &PartTwoModel), Some(0));
+        assert_eq!(grid.light_after(0, 0, 4, &PartTwoModel), None);
+
+        assert_eq!(grid.last_change(0, 0, &PartOneModel), Some(2));
+        assert_eq!(grid.last_change(1, 1, &PartOneModel), Some(3));
+        assert_eq!(grid.last_change(0, 2, &PartOneModel), None);
+
+        // The models disagree about whether turning on a light changes it.
+        assert_eq!(grid.last_change(2, 1, &PartOneModel), Some(0));
+        assert_eq!(grid.last_change(2, 1, &PartTwoModel), Some(3));
+
+        assert!(grid.undo(&PartTwoModel).is_some());
+        assert_eq!(grid.grid.brightness(&PartTwoModel), 9);
+        assert_eq!(grid.last_change(1, 1, &PartTwoModel), Some(1));
+
+        for _ in 0..3 {
+            assert!(grid.undo(&PartTwoModel).is_some());
+        }
+
+        assert!(grid.undo(&PartTwoModel).is_none());
+        assert_eq!(grid.grid.brightness(&PartTwoModel), 0);
+    }
+
+    /// Tests that invalid instructions are parse errors.
+    #[test]
+    fn invalid_instructions_are_errors() {
+        for (instruction, kind) in [
+            ("turn up 0,0 through 1,1", InstructionErrorKind::Invalid),
+            ("toggle 0,0 through 1", InstructionErrorKind::Invalid),
+            (
+                "toggle 0,0 through 1000,0",
+                InstructionErrorKind::OutOfRange,
+            ),
+            (
+                "toggle 0,0 through 0,1000",
+                InstructionErrorKind::OutOfRange,
+            ),
+            ("toggle 5,0 through 4,9", InstructionErrorKind::Inverted),
+            ("toggle 0,5 through 9,4", InstructionErrorKind::Inverted),
+        ] {
+            let error = parse_instruction(instruction, GRID_SIZE, GRID_SIZE, &PartOneModel).err();
+            assert_eq!(error, Some(InstructionError { instruction, kind }));
+        }
+
+        let error = InstructionError {
+            instruction: "toggle 5,0 through 4,9",
+            kind: InstructionErrorKind::Inverted,
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "rectangle is inverted: \"toggle 5,0 through 4,9\""
+        );
+
+        let result = parse_instruction(
+            "toggle 0,0 through 999,999",
+            GRID_SIZE,
+            GRID_SIZE,
+            &PartOneModel,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            part_one("turn on 0,0 through 1000,1000"),
+            Solution::ParseError
+        );
+    }
+
+    /// Tests writing PGM images.
+    #[test]
+    fn pgm_writing_works() {
+        const HEADER: &[u8] = b"P5\n3 2\n255\n";
+        const IMAGE_LEN: usize = HEADER.len() + 6;
+
+        let instructions = parse_instructions(
+            &[
+                "turn on 0,0 through 1,0",
+                "toggle 1,0 through 2,0",
+                "turn on 0,1 through 0,1",
+            ],
+            3,
+        );
+
+        let mut part_one_frames = Vec::new();
+        write_pgm_frames(&instructions, 3, 2, &PartOneModel, &mut part_one_frames)
+            .expect("writing to a vector should not fail");
+
+        let mut part_two_frames = Vec::new();
+        write_pgm_frames(&instructions, 3, 2, &PartTwoModel, &mut part_two_frames)
+            .expect("writing to a vector should not fail");
+
+        for frames in [&part_one_frames, &part_two_frames] {
+            assert_eq!(frames.len(), IMAGE_LEN * 3);
+
+            for frame in frames.chunks(IMAGE_LEN) {
+                assert!(frame.starts_with(HEADER));
+            }
+        }
+
+        // Part one is black and white.
+        let pixels = &part_one_frames[(IMAGE_LEN * 2 + HEADER.len())..];
+        assert_eq!(pixels, [255, 0, 255, 255, 0, 0]);
+
+        // Part two is scaled to the brightest light.
+        let pixels = &part_two_frames[(IMAGE_LEN * 2 + HEADER.len())..];
+        assert_eq!(pixels, [85, 255, 170, 85, 0, 0]);
+    }
+
+    /// Parses a slice of instructions for a square grid with a size.
+    fn parse_instructions(instructions: &[&str], size: u32) -> Vec<(Action, Rect)> {
+        instructions
+            .iter()
+            .map(|i| {
+                parse_instruction(i, size, size, &PartOneModel)
+                    .expect("instruction should be valid")
+            })
+            .collect()
+    }
+
+    /// Checks that a [`CompressedGrid`] matches a [`Grid`] with a
+    /// [`LightModel`].
+    fn check_compressed_grid(instructions: &[(Action, Rect)], model: &impl LightModel) {
+        let mut grid = Grid::new(GRID_SIZE, GRID_SIZE);
+        let mut compressed_grid = CompressedGrid::new(instructions.iter().map(|i| i.1));
+
+        for (action, rect) in instructions.iter().copied() {
+            grid.apply_action(action, rect, model);
+            compressed_grid.apply_action(action, rect, model);
+            assert_eq!(compressed_grid.brightness(model), grid.brightness(model));
+        }
+    }
+
+    /// A [`LightModel`] where brightness is capped and lights can be reset.
+    struct CappedModel {
+        /// The maximum brightness of a light.
+        cap: u16,
+    }
+
+    impl LightModel for CappedModel {
+        type Light = u16;
+
+        fn adjust_lights(&self, action: Action, lights: &mut [u16]) {
+            for light in lights {
+                *light = match action {
+                    Action::TurnOn => (*light + 1).min(self.cap),
+                    Action::TurnOff => light.saturating_sub(1),
+                    Action::Toggle => (*light + 2).min(self.cap),
+                    Action::Custom(_) => 0,
+                };
+            }
+        }
+
+        fn brightness(&self, light: u16) -> u64 {
+            u64::from(light)
+        }
+
+        fn parse_extra_verb(&self, verb: &str) -> Option<u8> {
+            (verb == "reset").then_some(0)
+        }
+    }
+
+    /// A [`LightModel`] where lights mix red, green, and blue.
+    struct ColourModel;
+
+    impl LightModel for ColourModel {
+        type Light = [bool; 3];
+
+        fn adjust_lights(&self, action: Action, lights: &mut [[bool; 3]]) {
+            for light in lights {
+                match action {
+                    Action::TurnOn => *light = [true; 3],
+                    Action::TurnOff => *light = [false; 3],
+                    Action::Toggle => light.iter_mut().for_each(|c| *c = !*c),
+                    Action::Custom(channel) => light[usize::from(channel)] = true,
+                }
+            }
+        }
+
+        fn brightness(&self, light: [bool; 3]) -> u64 {
+            light.iter().map(|&c| u64::from(c)).sum()
+        }
+
+        fn parse_extra_verb(&self, verb: &str) -> Option<u8> {
+            match verb {
+                "paint red" => Some(0),
+                "paint green" => Some(1),
+                "paint blue" => Some(2),
+                _ => None,
+            }
+        }
+    }
+
     /// A [`Grid`] for testing.
-    struct TestGrid {
+    struct TestGrid<M: LightModel> {
         /// The [`Grid`].
-        grid: Grid,
+        grid: Grid<M::Light>,
 
-        /// The [`Adjuster`].
-        adjuster: Adjuster,
+        /// The [`LightModel`].
+        model: M,
     }
 
-    impl TestGrid {
-        /// Creates a new `TestGrid` from an [`Adjuster`].
-        fn new(adjuster: Adjuster) -> Self {
+    impl<M: LightModel> TestGrid<M> {
+        /// Creates a new `TestGrid` from a [`LightModel`].
+        fn new(model: M) -> Self {
             Self {
-                grid: Grid::new(),
-                adjuster,
+                grid: Grid::new(GRID_SIZE, GRID_SIZE),
+                model,
             }
         }
 
         /// Checks that an instruction produces an expected brightness.
-        fn check_instruction(&mut self, instruction: &str, brightness: u32) {
-            let (action, rect) =
-                parse_instruction(instruction).expect("instruction should be valid");
+        fn check_instruction(&mut self, instruction: &str, brightness: u64) {
+            let (action, rect) = parse_instruction(instruction, GRID_SIZE, GRID_SIZE, &self.model)
+                .expect("instruction should be valid");
 
-            self.grid.apply_action(action, rect, self.adjuster);
-            assert_eq!(self.grid.brightness(), brightness);
+            self.grid.apply_action(action, rect, &self.model);
+            assert_eq!(self.grid.brightness(&self.model), brightness);
         }
     }
 }
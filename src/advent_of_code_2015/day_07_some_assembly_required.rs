@@ -2,14 +2,22 @@
 //!
 //! [link]: https://adventofcode.com/2015/day/7
 
-use std::collections::{HashMap, VecDeque};
+use std::{
+    any,
+    collections::{HashMap, VecDeque},
+    fmt::{self, Debug, Display, Formatter},
+    mem,
+    ops::{BitAnd, BitOr, BitXor, Not},
+    str::FromStr,
+};
 
 use crate::Solution;
 
 /// Solves part one.
 pub fn part_one(input: &str) -> Solution {
-    // Keep track of Bobby's circuit and instruction booklet.
-    let Some((mut circuit, instructions)) = create_circuit(input) else {
+    // Keep track of Bobby's circuit and instruction booklet. Each wire can carry
+    // a 16-bit signal.
+    let Some((mut circuit, instructions)) = create_circuit::<u16>(input) else {
         return Solution::ParseError;
     };
 
@@ -24,23 +32,38 @@ pub fn part_one(input: &str) -> Solution {
 
 /// Solves part two.
 pub fn part_two(input: &str) -> Solution {
-    // Start by doing the same thing as part one.
-    let Some((mut circuit, instructions)) = create_circuit(input) else {
+    // Start by doing the same thing as part one. This time the circuit is
+    // evaluated incrementally so that only the wires which depend on an
+    // overridden wire need to be evaluated again.
+    let Some((mut circuit, instructions)) = create_circuit::<u16>(input) else {
         return Solution::ParseError;
     };
 
-    let Some(signal) = follow_instructions(instructions.clone(), &mut circuit) else {
+    let output_id = circuit.get_wire_id("a");
+    let override_id = circuit.get_wire_id("b");
+
+    // Most of the circuit doesn't change when wire "b" is overridden, so it can
+    // be simplified first.
+    let Some((instructions, _)) =
+        optimize_instructions(&circuit, instructions, output_id, &[override_id])
+    else {
         return Solution::SolveError;
     };
 
-    // Override the signal on wire "b" with the signal from wire "a" and reset
-    // the other wires.
-    circuit.clear();
-    let wire_b_id = circuit.get_wire_id("b");
-    circuit.set_signal(wire_b_id, signal);
+    let Some(mut circuit) = IncrementalCircuit::new(circuit, instructions) else {
+        return Solution::SolveError;
+    };
 
-    // Follow the instructions again and find the new signal on wire "a".
-    if let Some(signal) = follow_instructions(instructions, &mut circuit) {
+    let Some(signal) = circuit.get_signal(output_id) else {
+        return Solution::SolveError;
+    };
+
+    // Override the signal on wire "b" with the signal from wire "a". This resets
+    // every wire that depends on wire "b".
+    circuit.override_signal(override_id, signal);
+
+    // Find the new signal on wire "a".
+    if let Some(signal) = circuit.get_signal(output_id) {
         signal.into()
     } else {
         Solution::SolveError
@@ -49,10 +72,10 @@ pub fn part_two(input: &str) -> Solution {
 
 /// Follows a queue of [`Instruction`]s on a circuit and returns the signal on
 /// wire "a". This function returns [`None`] if wire "a" has no signal.
-fn follow_instructions(
-    mut instructions: VecDeque<Instruction>,
-    circuit: &mut Circuit,
-) -> Option<u16> {
+fn follow_instructions<S: Signal>(
+    mut instructions: VecDeque<Instruction<S>>,
+    circuit: &mut Circuit<S>,
+) -> Option<S> {
     // Keep following instructions until there are none left to follow.
     while let Some(instruction) = instructions.pop_front() {
         if let Some(signal) = instruction.input.eval_signal(circuit) {
@@ -70,27 +93,191 @@ fn follow_instructions(
     circuit.get_signal(wire_a_id)
 }
 
+/// Optimizes a [`Circuit`]'s [`Instruction`]s for finding the signal on an
+/// output wire and returns the optimized [`Instruction`]s and the number of
+/// eliminated [`Gate`]s. [`Gate`]s with constant operands are folded into
+/// constants and [`Gate`]s which the output wire doesn't depend on are
+/// eliminated. Constants on kept wires are not folded so that the kept wires
+/// can still be overridden. This function returns [`None`] if a wire is
+/// connected to more than one [`Gate`].
+fn optimize_instructions<S: Signal>(
+    circuit: &Circuit<S>,
+    instructions: impl IntoIterator<Item = Instruction<S>>,
+    output_id: WireId,
+    kept_ids: &[WireId],
+) -> Option<(Vec<Instruction<S>>, usize)> {
+    let wire_count = circuit.wire_signals.len();
+    let mut gates = vec![None; wire_count];
+    let mut dependents = vec![Vec::new(); wire_count];
+    let mut gate_count = 0;
+
+    for instruction in instructions {
+        let output = usize::from(instruction.output.0);
+
+        for source in instruction.input.sources() {
+            if let Source::Wire(id) = source {
+                dependents[usize::from(id.0)].push(output);
+            }
+        }
+
+        if gates[output].replace(instruction.input).is_some() {
+            return None;
+        }
+
+        gate_count += 1;
+    }
+
+    let mut is_kept = vec![false; wire_count];
+
+    for id in kept_ids {
+        is_kept[usize::from(id.0)] = true;
+    }
+
+    // Fold constants into the gates which read them until there are no more
+    // constants to fold. A wire only becomes constant once, so its dependents
+    // only need to be revisited once.
+    let constant = |gate: Option<Gate<S>>| match gate {
+        Some(Gate::Unary(UnOp::Source, Source::Value(signal))) => Some(signal),
+        _ => None,
+    };
+
+    let mut queue: VecDeque<usize> = (0..wire_count).collect();
+
+    while let Some(wire) = queue.pop_front() {
+        let Some(gate) = gates[wire] else {
+            continue;
+        };
+
+        let was_constant = constant(Some(gate)).is_some();
+
+        let gate = gate.map_sources(|source| match source {
+            Source::Wire(id) if !is_kept[usize::from(id.0)] => {
+                constant(gates[usize::from(id.0)]).map_or(source, Source::Value)
+            }
+            _ => source,
+        });
+
+        let gate = gate.constant_signal().map_or(gate, |signal| {
+            Gate::Unary(UnOp::Source, Source::Value(signal))
+        });
+
+        gates[wire] = Some(gate);
+
+        if !was_constant && constant(Some(gate)).is_some() && !is_kept[wire] {
+            queue.extend(dependents[wire].iter().copied());
+        }
+    }
+
+    // Keep only the gates which the output wire depends on.
+    let mut is_used = vec![false; wire_count];
+    let mut stack = vec![output_id];
+    let mut optimized_instructions = Vec::new();
+
+    while let Some(id) = stack.pop() {
+        let wire = usize::from(id.0);
+
+        if mem::replace(&mut is_used[wire], true) {
+            continue;
+        }
+
+        if let Some(gate) = gates[wire] {
+            optimized_instructions.push(Instruction {
+                input: gate,
+                output: id,
+            });
+
+            stack.extend(gate.sources().filter_map(|source| match source {
+                Source::Value(_) => None,
+                Source::Wire(id) => Some(id),
+            }));
+        }
+    }
+
+    let eliminated_gate_count = gate_count - optimized_instructions.len();
+    Some((optimized_instructions, eliminated_gate_count))
+}
+
+/// Sorts a [`Circuit`]'s [`Instruction`]s so that each [`Gate`] comes after
+/// the [`Gate`]s it depends on. This function returns [`None`] if a wire is
+/// connected to more than one [`Gate`] or wires are connected in a loop.
+#[allow(dead_code, reason = "instructions are only sorted for code generation")]
+fn sort_instructions<S: Signal>(
+    circuit: &Circuit<S>,
+    instructions: impl IntoIterator<Item = Instruction<S>>,
+) -> Option<Vec<Instruction<S>>> {
+    let wire_count = circuit.wire_signals.len();
+    let mut gates = vec![None; wire_count];
+
+    for instruction in instructions {
+        if gates[usize::from(instruction.output.0)]
+            .replace(instruction.input)
+            .is_some()
+        {
+            return None;
+        }
+    }
+
+    // Count the wires with gates which each gate is waiting for.
+    let mut dependents = vec![Vec::new(); wire_count];
+    let mut waiting_counts = vec![0; wire_count];
+
+    for (wire, gate) in gates.iter().enumerate() {
+        let Some(gate) = gate else {
+            continue;
+        };
+
+        for source in gate.sources() {
+            if let Source::Wire(id) = source
+                && gates[usize::from(id.0)].is_some()
+            {
+                dependents[usize::from(id.0)].push(wire);
+                waiting_counts[wire] += 1;
+            }
+        }
+    }
+
+    let mut queue: VecDeque<usize> = (0..wire_count)
+        .filter(|&w| gates[w].is_some() && waiting_counts[w] == 0)
+        .collect();
+
+    let mut sorted_instructions = Vec::new();
+
+    while let Some(wire) = queue.pop_front() {
+        sorted_instructions.push(Instruction {
+            input: gates[wire]?,
+            output: WireId(wire.try_into().ok()?),
+        });
+
+        for &dependent in &dependents[wire] {
+            waiting_counts[dependent] -= 1;
+
+            if waiting_counts[dependent] == 0 {
+                queue.push_back(dependent);
+            }
+        }
+    }
+
+    // Any gates which were never sorted must be waiting for each other.
+    let gate_count = gates.iter().flatten().count();
+    (sorted_instructions.len() == gate_count).then_some(sorted_instructions)
+}
+
 /// A circuit of wires with optional signals.
 #[derive(Default)]
-struct Circuit {
+struct Circuit<S> {
     /// The map of identifiers to [`WireId`]s.
     wire_ids: HashMap<String, WireId>,
 
     /// The signals of each wire.
-    wire_signals: Vec<Option<u16>>,
+    wire_signals: Vec<Option<S>>,
 }
 
-impl Circuit {
+impl<S: Signal> Circuit<S> {
     /// Creates a new `Circuit`.
     fn new() -> Self {
         Self::default()
     }
 
-    /// Clears the `Circuit`'s signals.
-    fn clear(&mut self) {
-        self.wire_signals.fill(None);
-    }
-
     /// Returns a [`WireId`] from an identifier.
     fn get_wire_id(&mut self, identifier: &str) -> WireId {
         if let Some(wire_id) = self.wire_ids.get(identifier).copied() {
@@ -111,16 +298,244 @@ impl Circuit {
 
     /// Returns a wire's signal from its [`WireId`]. This function returns
     /// [`None`] if the wire has no signal.
-    fn get_signal(&self, id: WireId) -> Option<u16> {
+    fn get_signal(&self, id: WireId) -> Option<S> {
         self.wire_signals[usize::from(id.0)]
     }
 
     /// Sets a wire's signal from its [`WireId`].
-    fn set_signal(&mut self, id: WireId, signal: u16) {
+    fn set_signal(&mut self, id: WireId, signal: S) {
         if self.get_signal(id).is_none() {
             self.wire_signals[usize::from(id.0)] = Some(signal);
         }
     }
+
+    /// Returns the identifiers of each wire, indexed by [`WireId`].
+    #[allow(dead_code, reason = "identifiers are only needed for debugging")]
+    fn wire_identifiers(&self) -> Vec<&str> {
+        let mut identifiers = vec![""; self.wire_signals.len()];
+
+        for (identifier, id) in &self.wire_ids {
+            identifiers[usize::from(id.0)] = identifier;
+        }
+
+        identifiers
+    }
+
+    /// Clears a wire's signal from its [`WireId`].
+    fn clear_signal(&mut self, id: WireId) {
+        self.wire_signals[usize::from(id.0)] = None;
+    }
+}
+
+/// A [`Circuit`] which evaluates signals on demand and only re-evaluates the
+/// wires which depend on overridden signals.
+struct IncrementalCircuit<S> {
+    /// The [`Circuit`] of known signals.
+    circuit: Circuit<S>,
+
+    /// The [`Gate`] connected to each wire.
+    gates: Vec<Option<Gate<S>>>,
+
+    /// The wires which take a signal from each wire.
+    dependents: Vec<Vec<WireId>>,
+
+    /// Whether each wire's signal is overridden.
+    overridden: Vec<bool>,
+}
+
+impl<S: Signal> IncrementalCircuit<S> {
+    /// Creates a new `IncrementalCircuit` from a [`Circuit`] and its
+    /// [`Instruction`]s. This function returns [`None`] if a wire is connected
+    /// to more than one [`Gate`].
+    fn new(
+        circuit: Circuit<S>,
+        instructions: impl IntoIterator<Item = Instruction<S>>,
+    ) -> Option<Self> {
+        let wire_count = circuit.wire_signals.len();
+        let mut gates = vec![None; wire_count];
+        let mut dependents = vec![Vec::new(); wire_count];
+
+        for instruction in instructions {
+            for source in instruction.input.sources() {
+                if let Source::Wire(id) = source {
+                    dependents[usize::from(id.0)].push(instruction.output);
+                }
+            }
+
+            if gates[usize::from(instruction.output.0)]
+                .replace(instruction.input)
+                .is_some()
+            {
+                return None;
+            }
+        }
+
+        Some(Self {
+            circuit,
+            gates,
+            dependents,
+            overridden: vec![false; wire_count],
+        })
+    }
+
+    /// Returns a wire's signal from its [`WireId`], evaluating it if it is not
+    /// known. This function returns [`None`] if the wire has no signal or is
+    /// connected in a loop.
+    fn get_signal(&mut self, id: WireId) -> Option<S> {
+        // Evaluate the wires with a stack instead of recursion so that long
+        // chains of wires can't overflow the call stack. A wire is visited when
+        // its sources are pushed, and evaluated when it is reached again.
+        let mut is_visited = vec![false; self.gates.len()];
+        let mut stack = vec![id];
+
+        while let Some(&id) = stack.last() {
+            if self.circuit.get_signal(id).is_some() {
+                stack.pop();
+                continue;
+            }
+
+            let gate = self.gates[usize::from(id.0)]?;
+
+            if mem::replace(&mut is_visited[usize::from(id.0)], true) {
+                let signal = gate.eval_signal(&self.circuit)?;
+                self.circuit.set_signal(id, signal);
+                stack.pop();
+                continue;
+            }
+
+            for source in gate.sources() {
+                if let Source::Wire(source_id) = source
+                    && self.circuit.get_signal(source_id).is_none()
+                {
+                    // Any visited wire without a signal is still waiting for
+                    // this wire, so they must be connected in a loop.
+                    if is_visited[usize::from(source_id.0)] {
+                        return None;
+                    }
+
+                    stack.push(source_id);
+                }
+            }
+        }
+
+        self.circuit.get_signal(id)
+    }
+
+    /// Overrides a wire's signal from its [`WireId`].
+    fn override_signal(&mut self, id: WireId, signal: S) {
+        self.overridden[usize::from(id.0)] = true;
+        self.circuit.clear_signal(id);
+        self.circuit.set_signal(id, signal);
+        self.invalidate_dependents(id);
+    }
+
+    /// Removes a wire's overridden signal from its [`WireId`].
+    #[cfg(test)]
+    fn remove_override(&mut self, id: WireId) {
+        if mem::take(&mut self.overridden[usize::from(id.0)]) {
+            self.circuit.clear_signal(id);
+            self.invalidate_dependents(id);
+        }
+    }
+
+    /// Clears the signals of every wire which depends on a wire from its
+    /// [`WireId`], except for overridden wires.
+    fn invalidate_dependents(&mut self, id: WireId) {
+        let mut stack = self.dependents[usize::from(id.0)].clone();
+
+        while let Some(id) = stack.pop() {
+            // A wire with no signal can't have dependents with signals, so
+            // there's no need to follow it.
+            if self.overridden[usize::from(id.0)] || self.circuit.get_signal(id).is_none() {
+                continue;
+            }
+
+            self.circuit.clear_signal(id);
+            stack.extend_from_slice(&self.dependents[usize::from(id.0)]);
+        }
+    }
+}
+
+/// A wire in an [`IncrementalCircuit`] which is displayed as an indented tree
+/// of the [`Gate`]s and signals which produced its signal. Wires are only
+/// expanded the first time they appear in the tree.
+#[allow(dead_code, reason = "wires are only traced for debugging")]
+struct WireTrace<'a, S> {
+    /// The [`IncrementalCircuit`].
+    circuit: &'a IncrementalCircuit<S>,
+
+    /// The wire's [`WireId`].
+    id: WireId,
+}
+
+#[allow(dead_code, reason = "wires are only traced for debugging")]
+impl<S: Signal> WireTrace<'_, S> {
+    /// Formats a wire and the wires it depends on at a depth in the tree.
+    fn fmt_wire(
+        &self,
+        f: &mut Formatter<'_>,
+        identifiers: &[&str],
+        id: WireId,
+        depth: usize,
+        is_traced: &mut [bool],
+    ) -> fmt::Result {
+        let wire = usize::from(id.0);
+        write!(
+            f,
+            "{:indent$}{} = ",
+            "",
+            identifiers[wire],
+            indent = depth * 2
+        )?;
+
+        if let Some(signal) = self.circuit.circuit.get_signal(id) {
+            write!(f, "{signal}")?;
+        } else {
+            f.write_str("?")?;
+        }
+
+        if self.circuit.overridden[wire] {
+            return writeln!(f, " (overridden)");
+        }
+
+        let Some(gate) = self.circuit.gates[wire] else {
+            return writeln!(f, " (no gate)");
+        };
+
+        if mem::replace(&mut is_traced[wire], true) {
+            return writeln!(f, " (see above)");
+        }
+
+        let source = |source: Source<S>| match source {
+            Source::Value(value) => value.to_string(),
+            Source::Wire(id) => identifiers[usize::from(id.0)].to_owned(),
+        };
+
+        match gate {
+            Gate::Unary(UnOp::Source, rhs) => writeln!(f, " <- {}", source(rhs))?,
+            Gate::Unary(UnOp::Not, rhs) => writeln!(f, " <- NOT {}", source(rhs))?,
+            Gate::Binary(op, lhs, rhs) => {
+                let (lhs, op, rhs) = (source(lhs), op.keyword(), source(rhs));
+                writeln!(f, " <- {lhs} {op} {rhs}")?;
+            }
+        }
+
+        for source in gate.sources() {
+            if let Source::Wire(id) = source {
+                self.fmt_wire(f, identifiers, id, depth + 1, is_traced)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Signal> Display for WireTrace<'_, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let identifiers = self.circuit.circuit.wire_identifiers();
+        let mut is_traced = vec![false; identifiers.len()];
+        self.fmt_wire(f, &identifiers, self.id, 0, &mut is_traced)
+    }
 }
 
 /// A unique identifier for a wire.
@@ -129,28 +544,28 @@ struct WireId(u16);
 
 /// An instruction for connecting wires.
 #[derive(Clone)]
-struct Instruction {
+struct Instruction<S> {
     /// The input [`Gate`].
-    input: Gate,
+    input: Gate<S>,
 
     /// The output [`WireId`].
     output: WireId,
 }
 
 /// A logic gate.
-#[derive(Clone)]
-enum Gate {
+#[derive(Clone, Copy)]
+enum Gate<S> {
     /// A unary operation.
-    Unary(UnOp, Source),
+    Unary(UnOp, Source<S>),
 
     /// A binary operation.
-    Binary(BinOp, Source, Source),
+    Binary(BinOp, Source<S>, Source<S>),
 }
 
-impl Gate {
+impl<S: Signal> Gate<S> {
     /// Evaluates the `Gate`'s signal. with a [`Circuit`]. This function returns
     /// [`None`] if the `Gate` has no signal.
-    fn eval_signal(&self, circuit: &Circuit) -> Option<u16> {
+    fn eval_signal(&self, circuit: &Circuit<S>) -> Option<S> {
         let signal = match self {
             Self::Unary(op, rhs) => {
                 let rhs = rhs.eval_signal(circuit)?;
@@ -165,6 +580,37 @@ impl Gate {
 
         Some(signal)
     }
+
+    /// Returns the `Gate`'s signal if its [`Source`]s are all values. This
+    /// function returns [`None`] if any of the `Gate`'s [`Source`]s are wires.
+    fn constant_signal(self) -> Option<S> {
+        match self {
+            Self::Unary(op, Source::Value(rhs)) => Some(op.eval_signal(rhs)),
+            Self::Binary(op, Source::Value(lhs), Source::Value(rhs)) => {
+                Some(op.eval_signal(lhs, rhs))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a new `Gate` with a function applied to each of its
+    /// [`Source`]s.
+    fn map_sources(self, mut f: impl FnMut(Source<S>) -> Source<S>) -> Self {
+        match self {
+            Self::Unary(op, rhs) => Self::Unary(op, f(rhs)),
+            Self::Binary(op, lhs, rhs) => Self::Binary(op, f(lhs), f(rhs)),
+        }
+    }
+
+    /// Returns an iterator over the `Gate`'s [`Source`]s.
+    fn sources(self) -> impl Iterator<Item = Source<S>> {
+        let sources = match self {
+            Self::Unary(_, rhs) => [Some(rhs), None],
+            Self::Binary(_, lhs, rhs) => [Some(lhs), Some(rhs)],
+        };
+
+        sources.into_iter().flatten()
+    }
 }
 
 /// A unary operator.
@@ -179,7 +625,7 @@ enum UnOp {
 
 impl UnOp {
     /// Evaluates the `UnOp`'s signal from its operand signal.
-    fn eval_signal(self, rhs: u16) -> u16 {
+    fn eval_signal<S: Signal>(self, rhs: S) -> S {
         match self {
             Self::Source => rhs,
             Self::Not => !rhs,
@@ -196,39 +642,105 @@ enum BinOp {
     /// A bitwise or.
     Or,
 
+    /// A bitwise exclusive or.
+    Xor,
+
+    /// A bitwise not and.
+    Nand,
+
+    /// A bitwise not or.
+    Nor,
+
+    /// A wrapping addition.
+    Add,
+
+    /// A wrapping subtraction.
+    Subtract,
+
+    /// A wrapping multiplication.
+    Multiply,
+
     /// A bitwise left shift.
     LeftShift,
 
     /// A bitwise right shift.
     RightShift,
+
+    /// A bitwise left rotation.
+    LeftRotate,
+
+    /// A bitwise right rotation.
+    RightRotate,
 }
 
 impl BinOp {
+    /// Every `BinOp`.
+    const ALL: [Self; 12] = [
+        Self::And,
+        Self::Or,
+        Self::Xor,
+        Self::Nand,
+        Self::Nor,
+        Self::Add,
+        Self::Subtract,
+        Self::Multiply,
+        Self::LeftShift,
+        Self::RightShift,
+        Self::LeftRotate,
+        Self::RightRotate,
+    ];
+
+    /// Returns the `BinOp`'s keyword in an instruction booklet.
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::And => "AND",
+            Self::Or => "OR",
+            Self::Xor => "XOR",
+            Self::Nand => "NAND",
+            Self::Nor => "NOR",
+            Self::Add => "ADD",
+            Self::Subtract => "SUB",
+            Self::Multiply => "MUL",
+            Self::LeftShift => "LSHIFT",
+            Self::RightShift => "RSHIFT",
+            Self::LeftRotate => "LROTATE",
+            Self::RightRotate => "RROTATE",
+        }
+    }
+
     /// Evaluates the `BinOp`'s signal from its operand signals.
-    fn eval_signal(self, lhs: u16, rhs: u16) -> u16 {
+    fn eval_signal<S: Signal>(self, lhs: S, rhs: S) -> S {
         match self {
             Self::And => lhs & rhs,
             Self::Or => lhs | rhs,
-            Self::LeftShift => lhs << rhs,
-            Self::RightShift => lhs >> rhs,
+            Self::Xor => lhs ^ rhs,
+            Self::Nand => !(lhs & rhs),
+            Self::Nor => !(lhs | rhs),
+            Self::Add => lhs.wrapping_add(rhs),
+            Self::Subtract => lhs.wrapping_sub(rhs),
+            Self::Multiply => lhs.wrapping_mul(rhs),
+            Self::LeftShift => lhs.shift_left(rhs),
+            Self::RightShift => lhs.shift_right(rhs),
+            Self::LeftRotate => lhs.rotate_left_by(rhs),
+            Self::RightRotate => lhs.rotate_right_by(rhs),
         }
     }
 }
 
 /// A signal source.
 #[derive(Clone, Copy)]
-enum Source {
+enum Source<S> {
     /// A specific value.
-    Value(u16),
+    Value(S),
 
     /// A wire.
     Wire(WireId),
 }
 
-impl Source {
+impl<S: Signal> Source<S> {
     /// Evaluates the `Source`'s signal with a [`Circuit`]. This function
     /// returns [`None`] if the `Source` has no signal.
-    fn eval_signal(self, circuit: &Circuit) -> Option<u16> {
+    fn eval_signal(self, circuit: &Circuit<S>) -> Option<S> {
         match self {
             Self::Value(signal) => Some(signal),
             Self::Wire(id) => circuit.get_signal(id),
@@ -236,10 +748,104 @@ impl Source {
     }
 }
 
+/// A trait for unsigned integer types which may be used as signals.
+trait Signal:
+    Copy
+    + Default
+    + Eq
+    + Debug
+    + Display
+    + FromStr
+    + Not<Output = Self>
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+{
+    /// The width of the `Signal` in bits.
+    #[allow(dead_code, reason = "circuits are only exported for debugging")]
+    const BITS: u32;
+
+    /// Adds another `Signal` to the `Signal`, wrapping around on overflow.
+    fn wrapping_add(self, rhs: Self) -> Self;
+
+    /// Subtracts another `Signal` from the `Signal`, wrapping around on
+    /// overflow.
+    fn wrapping_sub(self, rhs: Self) -> Self;
+
+    /// Multiplies the `Signal` by another `Signal`, wrapping around on
+    /// overflow.
+    fn wrapping_mul(self, rhs: Self) -> Self;
+
+    /// Shifts the `Signal` left by another `Signal`. Shifting by at least the
+    /// width of the `Signal` returns zero.
+    fn shift_left(self, rhs: Self) -> Self;
+
+    /// Shifts the `Signal` right by another `Signal`. Shifting by at least the
+    /// width of the `Signal` returns zero.
+    fn shift_right(self, rhs: Self) -> Self;
+
+    /// Rotates the `Signal` left by another `Signal`, modulo the width of the
+    /// `Signal`.
+    fn rotate_left_by(self, rhs: Self) -> Self;
+
+    /// Rotates the `Signal` right by another `Signal`, modulo the width of the
+    /// `Signal`.
+    fn rotate_right_by(self, rhs: Self) -> Self;
+}
+
+/// Implements [`Signal`] for unsigned integer types.
+macro_rules! impl_signal {
+    ($($type:ty),*) => {$(
+        impl Signal for $type {
+            const BITS: u32 = <$type>::BITS;
+
+            fn wrapping_add(self, rhs: Self) -> Self {
+                self.wrapping_add(rhs)
+            }
+
+            fn wrapping_sub(self, rhs: Self) -> Self {
+                self.wrapping_sub(rhs)
+            }
+
+            fn wrapping_mul(self, rhs: Self) -> Self {
+                self.wrapping_mul(rhs)
+            }
+
+            fn shift_left(self, rhs: Self) -> Self {
+                u32::try_from(rhs)
+                    .ok()
+                    .and_then(|rhs| self.checked_shl(rhs))
+                    .unwrap_or(0)
+            }
+
+            fn shift_right(self, rhs: Self) -> Self {
+                u32::try_from(rhs)
+                    .ok()
+                    .and_then(|rhs| self.checked_shr(rhs))
+                    .unwrap_or(0)
+            }
+
+            fn rotate_left_by(self, rhs: Self) -> Self {
+                // Every signal width divides 64, so this doesn't change the
+                // rotation.
+                let rhs = u32::try_from(rhs % 64).expect("rotation should be less than 64");
+                self.rotate_left(rhs)
+            }
+
+            fn rotate_right_by(self, rhs: Self) -> Self {
+                let rhs = u32::try_from(rhs % 64).expect("rotation should be less than 64");
+                self.rotate_right(rhs)
+            }
+        }
+    )*};
+}
+
+impl_signal!(u8, u16, u32, u64);
+
 /// Creates a new [`Circuit`] and queue of [`Instruction`]s from an instruction
 /// booklet. This function returns [`None`] if the instruction booklet could not
 /// be parsed.
-fn create_circuit(booklet: &str) -> Option<(Circuit, VecDeque<Instruction>)> {
+fn create_circuit<S: Signal>(booklet: &str) -> Option<(Circuit<S>, VecDeque<Instruction<S>>)> {
     let mut circuit = Circuit::new();
     let mut instructions = VecDeque::new();
 
@@ -253,7 +859,7 @@ fn create_circuit(booklet: &str) -> Option<(Circuit, VecDeque<Instruction>)> {
 
 /// Parses an [`Instruction`] from a line of text with a [`Circuit`]. This
 /// function returns [`None`] if an [`Instruction`] could not be parsed.
-fn parse_instruction(line: &str, circuit: &mut Circuit) -> Option<Instruction> {
+fn parse_instruction<S: Signal>(line: &str, circuit: &mut Circuit<S>) -> Option<Instruction<S>> {
     let mut words = line.split(' ');
 
     let input = match words.next()? {
@@ -268,13 +874,7 @@ fn parse_instruction(line: &str, circuit: &mut Circuit) -> Option<Instruction> {
             match words.next()? {
                 "->" => Gate::Unary(UnOp::Source, lhs),
                 word => {
-                    let op = match word {
-                        "AND" => BinOp::And,
-                        "OR" => BinOp::Or,
-                        "LSHIFT" => BinOp::LeftShift,
-                        "RSHIFT" => BinOp::RightShift,
-                        _ => return None,
-                    };
+                    let op = BinOp::ALL.into_iter().find(|op| op.keyword() == word)?;
 
                     let rhs = parse_source(words.next()?, circuit)?;
                     words.next(); // Skip "->".
@@ -290,7 +890,7 @@ fn parse_instruction(line: &str, circuit: &mut Circuit) -> Option<Instruction> {
 
 /// Parses a [`Source`] from a word with a [`Circuit`]. This function returns
 /// [`None`] if a [`Source`] could not be parsed.
-fn parse_source(word: &str, circuit: &mut Circuit) -> Option<Source> {
+fn parse_source<S: Signal>(word: &str, circuit: &mut Circuit<S>) -> Option<Source<S>> {
     let source = if word.chars().next()?.is_ascii_digit() {
         Source::Value(word.parse().ok()?)
     } else {
@@ -300,35 +900,413 @@ fn parse_source(word: &str, circuit: &mut Circuit) -> Option<Source> {
     Some(source)
 }
 
+/// A [`Circuit`] and its [`Instruction`]s which are displayed as a Graphviz
+/// DOT graph. Each [`Gate`] is a node and each wire is an edge.
+#[allow(dead_code, reason = "circuits are only exported for debugging")]
+struct DotExport<'a, S> {
+    /// The [`Circuit`].
+    circuit: &'a Circuit<S>,
+
+    /// The [`Instruction`]s.
+    instructions: &'a [Instruction<S>],
+}
+
+impl<S: Signal> Display for DotExport<'_, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let identifiers = self.circuit.wire_identifiers();
+        let mut drivers = vec![None; identifiers.len()];
+        let mut is_read = vec![false; identifiers.len()];
+
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            drivers[usize::from(instruction.output.0)] = Some(index);
+        }
+
+        writeln!(f, "digraph circuit {{")?;
+
+        // Wires with no gate are inputs to the circuit.
+        for (index, identifier) in identifiers.iter().enumerate() {
+            if drivers[index].is_none() {
+                writeln!(f, "    w{index} [shape=plaintext, label=\"{identifier}\"];")?;
+            }
+        }
+
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let (shape, label) = match instruction.input {
+                Gate::Unary(UnOp::Source, _) => ("point", ""),
+                Gate::Unary(UnOp::Not, _) => ("invtriangle", "NOT"),
+                Gate::Binary(op, _, _) => ("box", op.keyword()),
+            };
+
+            writeln!(f, "    g{index} [shape={shape}, label=\"{label}\"];")?;
+
+            for (operand, source) in instruction.input.sources().enumerate() {
+                match source {
+                    Source::Value(value) => {
+                        writeln!(
+                            f,
+                            "    c{index}_{operand} [shape=plaintext, label=\"{value}\"];"
+                        )?;
+                        writeln!(f, "    c{index}_{operand} -> g{index};")?;
+                    }
+                    Source::Wire(id) => {
+                        let wire = usize::from(id.0);
+                        is_read[wire] = true;
+                        let identifier = identifiers[wire];
+
+                        if let Some(driver) = drivers[wire] {
+                            writeln!(f, "    g{driver} -> g{index} [label=\"{identifier}\"];")?;
+                        } else {
+                            writeln!(f, "    w{wire} -> g{index};")?;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Wires which are never read are outputs from the circuit.
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let wire = usize::from(instruction.output.0);
+
+            if !is_read[wire] {
+                let identifier = identifiers[wire];
+                writeln!(f, "    w{wire} [shape=plaintext, label=\"{identifier}\"];")?;
+                writeln!(f, "    g{index} -> w{wire} [label=\"{identifier}\"];")?;
+            }
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+/// A [`Circuit`] and its [`Instruction`]s which are displayed as a Verilog
+/// module. Wires with no [`Gate`] are inputs and every other wire is an output.
+/// Wire identifiers are prefixed with `w_` so that they can't be mistaken for
+/// Verilog keywords.
+#[allow(dead_code, reason = "circuits are only exported for debugging")]
+struct VerilogExport<'a, S> {
+    /// The [`Circuit`].
+    circuit: &'a Circuit<S>,
+
+    /// The [`Instruction`]s.
+    instructions: &'a [Instruction<S>],
+}
+
+impl<S: Signal> Display for VerilogExport<'_, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let identifiers = self.circuit.wire_identifiers();
+        let mut is_driven = vec![false; identifiers.len()];
+
+        for instruction in self.instructions {
+            is_driven[usize::from(instruction.output.0)] = true;
+        }
+
+        let bits = S::BITS;
+        writeln!(f, "module circuit (")?;
+
+        for (index, identifier) in identifiers.iter().enumerate() {
+            let direction = if is_driven[index] { "output" } else { "input" };
+            let separator = if index + 1 < identifiers.len() {
+                ","
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "    {direction} wire [{}:0] w_{identifier}{separator}",
+                bits - 1
+            )?;
+        }
+
+        writeln!(f, ");")?;
+
+        for instruction in self.instructions {
+            let source = |source| match source {
+                Source::Value(value) => format!("{bits}'d{value}"),
+                Source::Wire(id) => format!("w_{}", identifiers[usize::from(id.0)]),
+            };
+
+            let expression = match instruction.input {
+                Gate::Unary(UnOp::Source, rhs) => source(rhs),
+                Gate::Unary(UnOp::Not, rhs) => format!("~{}", source(rhs)),
+                Gate::Binary(op, lhs, rhs) => {
+                    let (lhs, rhs) = (source(lhs), source(rhs));
+
+                    // Verilog has no rotation operator, so rotations are made
+                    // from a pair of shifts. Shifting by the full width gives
+                    // zero, so rotating by zero still works.
+                    match op {
+                        BinOp::And => format!("{lhs} & {rhs}"),
+                        BinOp::Or => format!("{lhs} | {rhs}"),
+                        BinOp::Xor => format!("{lhs} ^ {rhs}"),
+                        BinOp::Nand => format!("~({lhs} & {rhs})"),
+                        BinOp::Nor => format!("~({lhs} | {rhs})"),
+                        BinOp::Add => format!("{lhs} + {rhs}"),
+                        BinOp::Subtract => format!("{lhs} - {rhs}"),
+                        BinOp::Multiply => format!("{lhs} * {rhs}"),
+                        BinOp::LeftShift => format!("{lhs} << {rhs}"),
+                        BinOp::RightShift => format!("{lhs} >> {rhs}"),
+                        BinOp::LeftRotate => format!(
+                            "({lhs} << ({rhs} % {bits})) | ({lhs} >> ({bits} - {rhs} % {bits}))"
+                        ),
+                        BinOp::RightRotate => format!(
+                            "({lhs} >> ({rhs} % {bits})) | ({lhs} << ({bits} - {rhs} % {bits}))"
+                        ),
+                    }
+                }
+            };
+
+            let identifier = identifiers[usize::from(instruction.output.0)];
+            writeln!(f, "    assign w_{identifier} = {expression};")?;
+        }
+
+        writeln!(f, "endmodule")
+    }
+}
+
+/// A [`Circuit`] and its sorted [`Instruction`]s which are displayed as a
+/// standalone Rust function. The function takes the signals on a set of input
+/// wires and every wire with no [`Gate`], and returns the signals on every wire
+/// in [`WireId`] order. The [`Instruction`]s must be sorted with
+/// [`sort_instructions`].
+#[allow(dead_code, reason = "code is only generated for benchmarking")]
+struct RustExport<'a, S> {
+    /// The [`Circuit`].
+    circuit: &'a Circuit<S>,
+
+    /// The sorted [`Instruction`]s.
+    instructions: &'a [Instruction<S>],
+
+    /// The [`WireId`]s of the input wires.
+    input_ids: &'a [WireId],
+}
+
+impl<S: Signal> Display for RustExport<'_, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let identifiers = self.circuit.wire_identifiers();
+        let is_input = input_wires(&identifiers, self.instructions, self.input_ids);
+        let (signal_type, bits) = (any::type_name::<S>(), S::BITS);
+
+        writeln!(
+            f,
+            "/// Returns the signals on each wire: {}.",
+            identifiers.join(", ")
+        )?;
+        write!(f, "pub fn circuit(")?;
+
+        let inputs = identifiers.iter().zip(&is_input).filter(|(_, i)| **i);
+
+        for (index, (identifier, _)) in inputs.enumerate() {
+            let separator = if index > 0 { ", " } else { "" };
+            write!(f, "{separator}w_{identifier}: {signal_type}")?;
+        }
+
+        writeln!(f, ") -> [{signal_type}; {}] {{", identifiers.len())?;
+
+        for instruction in self.instructions {
+            let output = usize::from(instruction.output.0);
+
+            if is_input[output] {
+                continue;
+            }
+
+            let source = |source| match source {
+                Source::Value(value) => format!("{value}_{signal_type}"),
+                Source::Wire(id) => format!("w_{}", identifiers[usize::from(id.0)]),
+            };
+
+            let expression = match instruction.input {
+                Gate::Unary(UnOp::Source, rhs) => source(rhs),
+                Gate::Unary(UnOp::Not, rhs) => format!("!{}", source(rhs)),
+                Gate::Binary(op, lhs, rhs) => {
+                    let (lhs, rhs) = (source(lhs), source(rhs));
+
+                    match op {
+                        BinOp::And => format!("{lhs} & {rhs}"),
+                        BinOp::Or => format!("{lhs} | {rhs}"),
+                        BinOp::Xor => format!("{lhs} ^ {rhs}"),
+                        BinOp::Nand => format!("!({lhs} & {rhs})"),
+                        BinOp::Nor => format!("!({lhs} | {rhs})"),
+                        BinOp::Add => format!("{lhs}.wrapping_add({rhs})"),
+                        BinOp::Subtract => format!("{lhs}.wrapping_sub({rhs})"),
+                        BinOp::Multiply => format!("{lhs}.wrapping_mul({rhs})"),
+                        BinOp::LeftShift => {
+                            format!("if {rhs} < {bits} {{ {lhs} << {rhs} }} else {{ 0 }}")
+                        }
+                        BinOp::RightShift => {
+                            format!("if {rhs} < {bits} {{ {lhs} >> {rhs} }} else {{ 0 }}")
+                        }
+                        BinOp::LeftRotate => format!("{lhs}.rotate_left(({rhs} % {bits}) as u32)"),
+                        BinOp::RightRotate => {
+                            format!("{lhs}.rotate_right(({rhs} % {bits}) as u32)")
+                        }
+                    }
+                }
+            };
+
+            let identifier = identifiers[output];
+            writeln!(f, "    let w_{identifier}: {signal_type} = {expression};")?;
+        }
+
+        let signals: Vec<_> = identifiers.iter().map(|i| format!("w_{i}")).collect();
+        writeln!(f, "    [{}]", signals.join(", "))?;
+        writeln!(f, "}}")
+    }
+}
+
+/// A [`Circuit`] and its sorted [`Instruction`]s which are displayed as a
+/// standalone C function. The function takes the signals on a set of input
+/// wires and every wire with no [`Gate`], and writes the signals on every wire
+/// to an array in [`WireId`] order. The [`Instruction`]s must be sorted with
+/// [`sort_instructions`].
+#[allow(dead_code, reason = "code is only generated for benchmarking")]
+struct CExport<'a, S> {
+    /// The [`Circuit`].
+    circuit: &'a Circuit<S>,
+
+    /// The sorted [`Instruction`]s.
+    instructions: &'a [Instruction<S>],
+
+    /// The [`WireId`]s of the input wires.
+    input_ids: &'a [WireId],
+}
+
+impl<S: Signal> Display for CExport<'_, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let identifiers = self.circuit.wire_identifiers();
+        let is_input = input_wires(&identifiers, self.instructions, self.input_ids);
+
+        // Operands are converted to at least 32 bits before each operation so
+        // that they aren't promoted to signed integers, which could overflow.
+        let bits = S::BITS;
+        let signal_type = format!("uint{bits}_t");
+        let operand_type = format!("uint{}_t", bits.max(32));
+
+        writeln!(f, "#include <stdint.h>")?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "/* Writes the signals on each wire: {}. */",
+            identifiers.join(", ")
+        )?;
+        write!(f, "void circuit(")?;
+
+        let inputs = identifiers.iter().zip(&is_input).filter(|(_, i)| **i);
+
+        for (identifier, _) in inputs {
+            write!(f, "{signal_type} w_{identifier}, ")?;
+        }
+
+        writeln!(f, "{signal_type} signals[{}]) {{", identifiers.len().max(1))?;
+
+        for instruction in self.instructions {
+            let output = usize::from(instruction.output.0);
+
+            if is_input[output] {
+                continue;
+            }
+
+            let source = |source| match source {
+                Source::Value(value) => format!("({operand_type}){value}ull"),
+                Source::Wire(id) => format!("({operand_type})w_{}", identifiers[usize::from(id.0)]),
+            };
+
+            let expression = match instruction.input {
+                Gate::Unary(UnOp::Source, rhs) => source(rhs),
+                Gate::Unary(UnOp::Not, rhs) => format!("~{}", source(rhs)),
+                Gate::Binary(op, lhs, rhs) => {
+                    let (lhs, rhs) = (source(lhs), source(rhs));
+
+                    // Shifting by at least the width of an integer is undefined
+                    // behavior in C, so the shift amounts are checked first.
+                    match op {
+                        BinOp::And => format!("{lhs} & {rhs}"),
+                        BinOp::Or => format!("{lhs} | {rhs}"),
+                        BinOp::Xor => format!("{lhs} ^ {rhs}"),
+                        BinOp::Nand => format!("~({lhs} & {rhs})"),
+                        BinOp::Nor => format!("~({lhs} | {rhs})"),
+                        BinOp::Add => format!("{lhs} + {rhs}"),
+                        BinOp::Subtract => format!("{lhs} - {rhs}"),
+                        BinOp::Multiply => format!("{lhs} * {rhs}"),
+                        BinOp::LeftShift => format!("{rhs} < {bits} ? {lhs} << {rhs} : 0"),
+                        BinOp::RightShift => format!("{rhs} < {bits} ? {lhs} >> {rhs} : 0"),
+                        BinOp::LeftRotate => format!(
+                            "({lhs} << {rhs} % {bits}) | ({lhs} >> ({bits} - {rhs} % {bits}) % {bits})"
+                        ),
+                        BinOp::RightRotate => format!(
+                            "({lhs} >> {rhs} % {bits}) | ({lhs} << ({bits} - {rhs} % {bits}) % {bits})"
+                        ),
+                    }
+                }
+            };
+
+            let identifier = identifiers[output];
+            writeln!(
+                f,
+                "    {signal_type} w_{identifier} = ({signal_type})({expression});"
+            )?;
+        }
+
+        for (index, identifier) in identifiers.iter().enumerate() {
+            writeln!(f, "    signals[{index}] = w_{identifier};")?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+/// Returns whether each wire is an input to generated code from a slice of
+/// wire identifiers, [`Instruction`]s, and input [`WireId`]s. Wires with no
+/// [`Gate`] are always inputs.
+#[allow(dead_code, reason = "code is only generated for benchmarking")]
+fn input_wires<S>(
+    identifiers: &[&str],
+    instructions: &[Instruction<S>],
+    input_ids: &[WireId],
+) -> Vec<bool> {
+    let mut is_input = vec![true; identifiers.len()];
+
+    for instruction in instructions {
+        is_input[usize::from(instruction.output.0)] = false;
+    }
+
+    for id in input_ids {
+        is_input[usize::from(id.0)] = true;
+    }
+
+    is_input
+}
+
 #[cfg(test)]
 mod tests {
+    use std::fmt::Write as _;
+
     use super::*;
 
     /// Tests part one.
     #[test]
     fn part_one_works() {
-        check_booklet("123 -> x", &[("x", 123)]);
+        check_booklet::<u16>("123 -> x", &[("x", 123)]);
 
-        check_booklet(
+        check_booklet::<u16>(
             "x AND y -> z\n\
             3 -> x\n\
             6 -> y\n",
             &[("x", 3), ("y", 6), ("z", 2)],
         );
 
-        check_booklet(
+        check_booklet::<u16>(
             "p LSHIFT 2 -> q\n\
             100 -> p\n",
             &[("p", 100), ("q", 400)],
         );
 
-        check_booklet(
+        check_booklet::<u16>(
             "NOT e -> f\n\
             0 -> e",
             &[("e", 0), ("f", 65535)],
         );
 
-        check_booklet(
+        check_booklet::<u16>(
             "123 -> x\n\
             456 -> y\n\
             x AND y -> d\n\
@@ -350,10 +1328,420 @@ mod tests {
         );
     }
 
+    /// Tests circuits with different signal widths.
+    #[test]
+    fn signal_widths_work() {
+        let booklet = "NOT x -> y\n\
+            x LSHIFT 3 -> z\n\
+            x RSHIFT 1 -> w\n\
+            200 -> x\n";
+
+        check_booklet::<u8>(booklet, &[("y", 55), ("z", 64), ("w", 100)]);
+        check_booklet::<u32>(booklet, &[("y", 4_294_967_095), ("z", 1600), ("w", 100)]);
+
+        check_booklet::<u64>(
+            booklet,
+            &[("y", 18_446_744_073_709_551_415), ("z", 1600), ("w", 100)],
+        );
+
+        // Values which don't fit in the signal width can't be parsed.
+        assert!(create_circuit::<u8>("256 -> x").is_none());
+    }
+
+    /// Tests shifting by at least the signal width.
+    #[test]
+    fn wide_shifts_work() {
+        let booklet = "x LSHIFT 16 -> a\n\
+            x RSHIFT 16 -> b\n\
+            x LSHIFT 15 -> c\n\
+            x RSHIFT 15 -> d\n\
+            x LSHIFT 65535 -> e\n\
+            65535 -> x\n";
+
+        check_booklet::<u16>(
+            booklet,
+            &[("a", 0), ("b", 0), ("c", 32768), ("d", 1), ("e", 0)],
+        );
+
+        check_booklet::<u8>(
+            "x LSHIFT 8 -> a\n\
+            x RSHIFT 200 -> b\n\
+            255 -> x\n",
+            &[("a", 0), ("b", 0)],
+        );
+    }
+
+    /// Tests the extended gates.
+    #[test]
+    fn extended_gates_work() {
+        let booklet = "x XOR y -> a\n\
+            x NAND y -> b\n\
+            x NOR y -> c\n\
+            x ADD y -> d\n\
+            x SUB y -> e\n\
+            y SUB x -> f\n\
+            x MUL y -> g\n\
+            x LROTATE 4 -> h\n\
+            x RROTATE 4 -> i\n\
+            x LROTATE 20 -> j\n\
+            200 -> x\n\
+            100 -> y\n";
+
+        check_booklet::<u16>(
+            booklet,
+            &[
+                ("a", 172),
+                ("b", 65471),
+                ("c", 65299),
+                ("d", 300),
+                ("e", 100),
+                ("f", 65436),
+                ("g", 20000),
+                ("h", 3200),
+                ("i", 32780),
+                ("j", 3200),
+            ],
+        );
+
+        check_booklet::<u8>(
+            booklet,
+            &[
+                ("a", 172),
+                ("b", 191),
+                ("c", 19),
+                ("d", 44),
+                ("e", 100),
+                ("f", 156),
+                ("g", 32),
+                ("h", 140),
+                ("i", 140),
+                ("j", 140),
+            ],
+        );
+    }
+
+    /// Tests exporting a circuit as a Graphviz DOT graph.
+    #[test]
+    fn dot_export_works() {
+        let (circuit, instructions) = create_circuit::<u16>(
+            "x AND y -> z\n\
+            NOT z -> w\n\
+            3 -> x\n",
+        )
+        .expect("instruction booklet should be valid");
+
+        let instructions = Vec::from(instructions);
+        let dot = DotExport {
+            circuit: &circuit,
+            instructions: &instructions,
+        };
+
+        assert_eq!(
+            dot.to_string(),
+            "digraph circuit {\n    \
+            w1 [shape=plaintext, label=\"y\"];\n    \
+            g0 [shape=box, label=\"AND\"];\n    \
+            g2 -> g0 [label=\"x\"];\n    \
+            w1 -> g0;\n    \
+            g1 [shape=invtriangle, label=\"NOT\"];\n    \
+            g0 -> g1 [label=\"z\"];\n    \
+            g2 [shape=point, label=\"\"];\n    \
+            c2_0 [shape=plaintext, label=\"3\"];\n    \
+            c2_0 -> g2;\n    \
+            w3 [shape=plaintext, label=\"w\"];\n    \
+            g1 -> w3 [label=\"w\"];\n\
+            }\n"
+        );
+    }
+
+    /// Tests exporting a circuit as a Verilog module.
+    #[test]
+    fn verilog_export_works() {
+        let (circuit, instructions) = create_circuit::<u8>(
+            "x NAND y -> z\n\
+            NOT z -> w\n\
+            z LROTATE 3 -> v\n\
+            3 -> x\n",
+        )
+        .expect("instruction booklet should be valid");
+
+        let instructions = Vec::from(instructions);
+        let verilog = VerilogExport {
+            circuit: &circuit,
+            instructions: &instructions,
+        };
+
+        assert_eq!(
+            verilog.to_string(),
+            "module circuit (\n    \
+            output wire [7:0] w_x,\n    \
+            input wire [7:0] w_y,\n    \
+            output wire [7:0] w_z,\n    \
+            output wire [7:0] w_w,\n    \
+            output wire [7:0] w_v\n\
+            );\n    \
+            assign w_z = ~(w_x & w_y);\n    \
+            assign w_w = ~w_z;\n    \
+            assign w_v = (w_z << (8'd3 % 8)) | (w_z >> (8 - 8'd3 % 8));\n    \
+            assign w_x = 8'd3;\n\
+            endmodule\n"
+        );
+    }
+
+    /// Tests optimizing instructions.
+    #[test]
+    fn optimization_works() {
+        let booklet = "x AND y -> z\n\
+            NOT z -> w\n\
+            w OR k -> a\n\
+            3 -> x\n\
+            6 -> y\n\
+            1 LSHIFT 3 -> k\n\
+            a RSHIFT 1 -> u\n\
+            x OR 8 -> v\n";
+
+        let (mut circuit, instructions) =
+            create_circuit::<u16>(booklet).expect("instruction booklet should be valid");
+
+        let output_id = circuit.get_wire_id("a");
+        let kept_id = circuit.get_wire_id("y");
+
+        // Every gate can be folded into a constant on wire "a".
+        let (optimized_instructions, eliminated_gate_count) =
+            optimize_instructions(&circuit, instructions.clone(), output_id, &[])
+                .expect("circuit should be valid");
+
+        assert_eq!(optimized_instructions.len(), 1);
+        assert_eq!(eliminated_gate_count, 7);
+        assert_eq!(
+            optimized_instructions[0].input.constant_signal(),
+            Some(65533)
+        );
+
+        // Wire "y" is kept, so wires "z", "w", and "a" depend on it.
+        let (optimized_instructions, eliminated_gate_count) =
+            optimize_instructions(&circuit, instructions, output_id, &[kept_id])
+                .expect("circuit should be valid");
+
+        assert_eq!(optimized_instructions.len(), 4);
+        assert_eq!(eliminated_gate_count, 4);
+
+        let mut circuit = IncrementalCircuit::new(circuit, optimized_instructions)
+            .expect("circuit should be valid");
+
+        assert_eq!(circuit.get_signal(output_id), Some(65533));
+        circuit.override_signal(kept_id, 0);
+        assert_eq!(circuit.get_signal(output_id), Some(65535));
+    }
+
+    /// Tests tracing a wire's signal.
+    #[test]
+    fn wire_trace_works() {
+        let mut circuit = TestCircuit::new(
+            "x AND y -> z\n\
+            z OR x -> w\n\
+            NOT w -> v\n\
+            u -> t\n\
+            3 -> x\n\
+            6 -> y\n",
+        );
+
+        circuit.override_signal("y", 12);
+        circuit.check_signal("v", Some(65532));
+        circuit.check_signal("t", None);
+
+        assert_eq!(
+            circuit.trace("v"),
+            "v = 65532 <- NOT w\n  \
+            w = 3 <- z OR x\n    \
+            z = 0 <- x AND y\n      \
+            x = 3 <- 3\n      \
+            y = 12 (overridden)\n    \
+            x = 3 (see above)\n"
+        );
+
+        assert_eq!(circuit.trace("t"), "t = ? <- u\n  u = ? (no gate)\n");
+    }
+
+    /// Tests sorting instructions.
+    #[test]
+    fn instruction_sorting_works() {
+        let (circuit, instructions) = create_circuit::<u16>(
+            "x AND y -> z\n\
+            z OR z -> w\n\
+            3 -> x\n",
+        )
+        .expect("instruction booklet should be valid");
+
+        let sorted_instructions =
+            sort_instructions(&circuit, instructions).expect("circuit should be valid");
+
+        let outputs: Vec<_> = sorted_instructions.iter().map(|i| i.output.0).collect();
+        assert_eq!(outputs, [0, 2, 3]);
+
+        let (circuit, instructions) = create_circuit::<u16>(
+            "x -> y\n\
+            NOT y -> x\n",
+        )
+        .expect("instruction booklet should be valid");
+
+        assert!(sort_instructions(&circuit, instructions).is_none());
+    }
+
+    /// Tests generating Rust and C code.
+    #[test]
+    fn code_generation_works() {
+        let (mut circuit, instructions) = create_circuit::<u16>(
+            "x AND y -> z\n\
+            z LSHIFT 2 -> w\n\
+            w RROTATE x -> v\n\
+            3 -> x\n",
+        )
+        .expect("instruction booklet should be valid");
+
+        let input_ids = [circuit.get_wire_id("x")];
+        let instructions =
+            sort_instructions(&circuit, instructions).expect("circuit should be valid");
+
+        let rust = RustExport {
+            circuit: &circuit,
+            instructions: &instructions,
+            input_ids: &input_ids,
+        };
+
+        assert_eq!(
+            rust.to_string(),
+            "/// Returns the signals on each wire: x, y, z, w, v.\n\
+            pub fn circuit(w_x: u16, w_y: u16) -> [u16; 5] {\n    \
+            let w_z: u16 = w_x & w_y;\n    \
+            let w_w: u16 = if 2_u16 < 16 { w_z << 2_u16 } else { 0 };\n    \
+            let w_v: u16 = w_w.rotate_right((w_x % 16) as u32);\n    \
+            [w_x, w_y, w_z, w_w, w_v]\n\
+            }\n"
+        );
+
+        let c = CExport {
+            circuit: &circuit,
+            instructions: &instructions,
+            input_ids: &input_ids,
+        };
+
+        assert_eq!(
+            c.to_string(),
+            "#include <stdint.h>\n\
+            \n\
+            /* Writes the signals on each wire: x, y, z, w, v. */\n\
+            void circuit(uint16_t w_x, uint16_t w_y, uint16_t signals[5]) {\n    \
+            uint16_t w_z = (uint16_t)((uint32_t)w_x & (uint32_t)w_y);\n    \
+            uint16_t w_w = (uint16_t)((uint32_t)2ull < 16 ? (uint32_t)w_z << (uint32_t)2ull : 0);\n    \
+            uint16_t w_v = (uint16_t)(((uint32_t)w_w >> (uint32_t)w_x % 16) | \
+            ((uint32_t)w_w << (16 - (uint32_t)w_x % 16) % 16));\n    \
+            signals[0] = w_x;\n    \
+            signals[1] = w_y;\n    \
+            signals[2] = w_z;\n    \
+            signals[3] = w_w;\n    \
+            signals[4] = w_v;\n\
+            }\n"
+        );
+    }
+
+    /// Tests incremental evaluation.
+    #[test]
+    fn incremental_evaluation_works() {
+        let mut circuit = TestCircuit::new(
+            "x AND y -> z\n\
+            NOT z -> w\n\
+            x OR 8 -> v\n\
+            3 -> x\n\
+            6 -> y\n",
+        );
+
+        circuit.check_signal("w", Some(65533));
+        circuit.check_signal("v", Some(11));
+
+        // Overriding wire "y" should only reset wires "z" and "w".
+        circuit.override_signal("y", 7);
+        circuit.check_known_signal("x", Some(3));
+        circuit.check_known_signal("v", Some(11));
+        circuit.check_known_signal("z", None);
+        circuit.check_known_signal("w", None);
+        circuit.check_signal("w", Some(65532));
+
+        // Overridden wires should not be reset by the wires they depend on.
+        circuit.override_signal("z", 0);
+        circuit.override_signal("x", 1);
+        circuit.check_signal("w", Some(65535));
+        circuit.check_signal("v", Some(9));
+
+        // Removing every override should restore the original signals.
+        circuit.remove_override("z");
+        circuit.remove_override("y");
+        circuit.remove_override("x");
+        circuit.check_signal("w", Some(65533));
+        circuit.check_signal("v", Some(11));
+    }
+
+    /// Tests that wires connected in a loop have no signal.
+    #[test]
+    fn incremental_evaluation_detects_loops() {
+        let mut circuit = TestCircuit::new(
+            "x -> y\n\
+            NOT y -> x\n",
+        );
+
+        circuit.check_signal("x", None);
+
+        let mut circuit = TestCircuit::new(
+            "a AND b -> c\n\
+            b OR d -> a\n\
+            d -> b\n\
+            c -> d\n\
+            1 -> e\n\
+            e AND e -> f\n",
+        );
+
+        circuit.check_signal("a", None);
+        circuit.check_signal("f", Some(1));
+    }
+
+    /// Tests that long chains of wires are evaluated without recursion.
+    #[test]
+    fn incremental_evaluation_handles_long_chains() {
+        // Name each wire with base 26 letters.
+        const LETTERS: &[u8; 26] = b"abcdefghijklmnopqrstuvwxyz";
+
+        let identifier = |mut index: usize| {
+            let mut identifier = String::new();
+
+            loop {
+                identifier.insert(0, char::from(LETTERS[index % 26]));
+                index /= 26;
+
+                if index == 0 {
+                    break identifier;
+                }
+            }
+        };
+
+        let mut booklet = format!("123 -> {}\n", identifier(0));
+
+        for index in 1..50_000 {
+            let _ = writeln!(
+                booklet,
+                "{} -> {}",
+                identifier(index - 1),
+                identifier(index)
+            );
+        }
+
+        let mut circuit = TestCircuit::new(&booklet);
+        circuit.check_signal(&identifier(49_999), Some(123));
+    }
+
     /// Checks that an instruction booklet produces expected signals.
-    fn check_booklet(booklet: &str, signals: &[(&str, u16)]) {
+    fn check_booklet<S: Signal>(booklet: &str, signals: &[(&str, S)]) {
         let (mut circuit, instructions) =
-            create_circuit(booklet).expect("instruction booklet should be valid");
+            create_circuit::<S>(booklet).expect("instruction booklet should be valid");
 
         let _ = follow_instructions(instructions, &mut circuit);
 
@@ -363,4 +1751,53 @@ mod tests {
             assert_eq!(signal, expected_signal);
         }
     }
+
+    /// An [`IncrementalCircuit`] for testing.
+    struct TestCircuit(IncrementalCircuit<u16>);
+
+    impl TestCircuit {
+        /// Creates a new `TestCircuit` from an instruction booklet.
+        fn new(booklet: &str) -> Self {
+            let (circuit, instructions) =
+                create_circuit(booklet).expect("instruction booklet should be valid");
+
+            Self(IncrementalCircuit::new(circuit, instructions).expect("circuit should be valid"))
+        }
+
+        /// Returns a [`WireId`] from an identifier.
+        fn wire_id(&self, identifier: &str) -> WireId {
+            self.0.circuit.wire_ids[identifier]
+        }
+
+        /// Checks that a wire evaluates to an expected signal.
+        fn check_signal(&mut self, identifier: &str, expected_signal: Option<u16>) {
+            let wire_id = self.wire_id(identifier);
+            assert_eq!(self.0.get_signal(wire_id), expected_signal);
+        }
+
+        /// Checks that a wire has an expected signal without evaluating it.
+        fn check_known_signal(&self, identifier: &str, expected_signal: Option<u16>) {
+            let wire_id = self.wire_id(identifier);
+            assert_eq!(self.0.circuit.get_signal(wire_id), expected_signal);
+        }
+
+        /// Returns a wire's [`WireTrace`] as a string.
+        fn trace(&self, identifier: &str) -> String {
+            let id = self.wire_id(identifier);
+            let circuit = &self.0;
+            WireTrace { circuit, id }.to_string()
+        }
+
+        /// Overrides a wire's signal.
+        fn override_signal(&mut self, identifier: &str, signal: u16) {
+            let wire_id = self.wire_id(identifier);
+            self.0.override_signal(wire_id, signal);
+        }
+
+        /// Removes a wire's overridden signal.
+        fn remove_override(&mut self, identifier: &str) {
+            let wire_id = self.wire_id(identifier);
+            self.0.remove_override(wire_id);
+        }
+    }
 }
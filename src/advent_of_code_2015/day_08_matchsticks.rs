@@ -2,120 +2,583 @@
 //!
 //! [link]: https://adventofcode.com/2015/day/8
 
+use std::fmt::{self, Display, Formatter, Write as _};
+
 use crate::Solution;
 
 /// Solves part one.
 pub fn part_one(input: &str) -> Solution {
-    // Find the length of the strings in Santa's list, minus the length of the
-    // unescaped strings.
+    decoded_len_difference(input, LengthMode::Bytes)
+}
+
+/// Solves part two.
+pub fn part_two(input: &str) -> Solution {
+    encoded_len_difference(input, LengthMode::Bytes)
+}
+
+/// Returns the length of the strings in a list, minus the length of the
+/// decoded strings, counted with a [`LengthMode`].
+fn decoded_len_difference(input: &str, mode: LengthMode) -> Solution {
     let mut len_difference = 0;
 
     for string in input.lines() {
-        let len = string.len();
+        let len = mode.len(string);
+
+        let Ok(bytes) = decode(string) else {
+            return Solution::ParseError;
+        };
 
-        let Some(unescaped_len) = unescaped_len(string) else {
+        // Decoded strings may not be valid UTF-8.
+        let Some(decoded_len) = mode.checked_len(&bytes) else {
             return Solution::SolveError;
         };
 
-        len_difference += len - unescaped_len;
+        len_difference += len - decoded_len;
     }
 
     len_difference.into()
 }
 
-/// Solves part two.
-pub fn part_two(input: &str) -> Solution {
-    // Now we need to find the difference if we escaped the strings instead.
+/// Returns the length of the encoded strings in a list, minus the length of
+/// the strings, counted with a [`LengthMode`].
+fn encoded_len_difference(input: &str, mode: LengthMode) -> Solution {
     let mut len_difference = 0;
 
     for string in input.lines() {
-        let len = string.len();
-        len_difference += escaped_len(string) - len;
+        let len = mode.len(string);
+        len_difference += mode.len(&encode(string.as_bytes())) - len;
     }
 
     len_difference.into()
 }
 
-/// Returns the unescaped length of a string. This function returns [`None`] if
-/// the string is unterminated.
-fn unescaped_len(string: &str) -> Option<usize> {
-    // Keep track of the length.
-    let mut length = 0;
+/// A unit for counting the length of a string.
+#[derive(Clone, Copy)]
+enum LengthMode {
+    /// Count bytes.
+    Bytes,
 
-    // Iterate over the characters.
-    let mut chars = string.chars();
-    chars.next()?; // Skip the opening quote.
+    /// Count Unicode scalar values.
+    #[allow(dead_code, reason = "the puzzle's strings are ASCII")]
+    Chars,
 
-    loop {
-        let char = chars.next()?;
+    /// Count approximate grapheme clusters, which are what a reader sees as
+    /// characters. See [`approx_grapheme_count`] for which scripts are
+    /// counted correctly.
+    #[allow(dead_code, reason = "the puzzle's strings are ASCII")]
+    ApproxGraphemes,
+}
+
+impl LengthMode {
+    /// Returns the length of a string.
+    fn len(self, string: &str) -> usize {
+        self.checked_len(string.as_bytes())
+            .expect("string should be valid UTF-8")
+    }
+
+    /// Returns the length of a string. This function returns [`None`] if the
+    /// string is not valid UTF-8 and the `LengthMode` doesn't count bytes.
+    fn checked_len(self, bytes: &[u8]) -> Option<usize> {
+        match self {
+            Self::Bytes => Some(bytes.len()),
+            Self::Chars => Some(str::from_utf8(bytes).ok()?.chars().count()),
+            Self::ApproxGraphemes => Some(approx_grapheme_count(str::from_utf8(bytes).ok()?)),
+        }
+    }
+}
+
+/// Returns the approximate number of grapheme clusters in a string. Clusters
+/// are approximated by joining combining marks, Devanagari vowel signs and
+/// viramas, variation selectors, emoji modifiers, zero width joiner sequences,
+/// regional indicator pairs, Hangul jamo sequences, and `"\r\n"` to the
+/// character before them.
+///
+/// There are no Unicode character tables, so other clusters are split. This
+/// includes the marks of other Indic scripts, Thai, Tibetan, Myanmar, and
+/// Khmer, prepended characters, and consonants joined by a virama, which are
+/// one cluster since Unicode 15.1.
+fn approx_grapheme_count(string: &str) -> usize {
+    let mut count = 0;
+    let mut previous = None;
+    let mut regional_indicators = 0;
+
+    for char in string.chars() {
+        let is_regional_indicator = ('\u{1f1e6}'..='\u{1f1ff}').contains(&char);
+
+        let joins_previous = match previous {
+            None => false,
+            Some('\u{200d}') => true,
+            Some('\r') => char == '\n',
+            Some(previous) => {
+                is_extender(char)
+                    || (is_regional_indicator && regional_indicators % 2 == 1)
+                    || Hangul::of(previous)
+                        .zip(Hangul::of(char))
+                        .is_some_and(|(previous, next)| previous.joins(next))
+            }
+        };
+
+        if !joins_previous {
+            count += 1;
+        }
+
+        regional_indicators = if is_regional_indicator {
+            regional_indicators + 1
+        } else {
+            0
+        };
+
+        previous = Some(char);
+    }
+
+    count
+}
+
+/// Returns `true` if a character extends the grapheme cluster before it.
+fn is_extender(char: char) -> bool {
+    matches!(
+        char,
+        '\u{300}'..='\u{36f}'
+            | '\u{900}'..='\u{903}'
+            | '\u{93a}'..='\u{93c}'
+            | '\u{93e}'..='\u{94f}'
+            | '\u{951}'..='\u{957}'
+            | '\u{962}'..='\u{963}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{200d}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{fe20}'..='\u{fe2f}'
+            | '\u{1f3fb}'..='\u{1f3ff}'
+            | '\u{e0020}'..='\u{e007f}'
+    )
+}
 
-        // Stop on a closing quote, and skip the next character or hexadecimal
-        // digits after a backslash.
+/// A kind of Hangul character, for joining jamo into syllables.
+#[derive(Clone, Copy)]
+enum Hangul {
+    /// A leading consonant jamo.
+    Leading,
+
+    /// A vowel jamo.
+    Vowel,
+
+    /// A trailing consonant jamo.
+    Trailing,
+
+    /// A precomposed syllable without a trailing consonant.
+    LvSyllable,
+
+    /// A precomposed syllable with a trailing consonant.
+    LvtSyllable,
+}
+
+impl Hangul {
+    /// Returns the kind of `Hangul` character that a character is, or
+    /// [`None`] if it is not a Hangul jamo or syllable.
+    fn of(char: char) -> Option<Self> {
         match char {
-            '"' => return Some(length),
-            '\\' => {
-                if chars.next()? == 'x' {
-                    chars.next()?;
-                    chars.next()?;
+            '\u{1100}'..='\u{115f}' | '\u{a960}'..='\u{a97c}' => Some(Self::Leading),
+            '\u{1160}'..='\u{11a7}' | '\u{d7b0}'..='\u{d7c6}' => Some(Self::Vowel),
+            '\u{11a8}'..='\u{11ff}' | '\u{d7cb}'..='\u{d7fb}' => Some(Self::Trailing),
+
+            // Every 28th syllable has no trailing consonant.
+            '\u{ac00}'..='\u{d7a3}' if (u32::from(char) - 0xac00) % 28 == 0 => {
+                Some(Self::LvSyllable)
+            }
+            '\u{ac00}'..='\u{d7a3}' => Some(Self::LvtSyllable),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if a `Hangul` character joins this one in a syllable.
+    fn joins(self, next: Self) -> bool {
+        matches!(
+            (self, next),
+            (
+                Self::Leading,
+                Self::Leading | Self::Vowel | Self::LvSyllable | Self::LvtSyllable
+            ) | (Self::Vowel | Self::LvSyllable, Self::Vowel | Self::Trailing)
+                | (Self::Trailing | Self::LvtSyllable, Self::Trailing)
+        )
+    }
+}
+
+/// Decodes a quoted string literal into bytes. Unicode escapes are decoded as
+/// UTF-8. This function returns a [`DecodeError`] if the literal is not quoted
+/// or contains an invalid escape.
+fn decode(literal: &str) -> Result<Vec<u8>, DecodeError> {
+    let error = |position, kind| DecodeError { position, kind };
+    let literal = literal.as_bytes();
+
+    if literal.first() != Some(&b'"') {
+        return Err(error(0, DecodeErrorKind::MissingOpeningQuote));
+    }
+
+    let mut bytes = Vec::new();
+    let mut position = 1;
+
+    loop {
+        let Some(&byte) = literal.get(position) else {
+            return Err(error(position, DecodeErrorKind::Unterminated));
+        };
+
+        // Stop on a closing quote, and decode the next byte, hexadecimal
+        // digits, or Unicode escape after a backslash.
+        match byte {
+            b'"' => break,
+            b'\\' => match literal.get(position + 1) {
+                Some(&escaped_byte @ (b'"' | b'\\')) => {
+                    bytes.push(escaped_byte);
+                    position += 2;
+                }
+                Some(b'x') => {
+                    let high = parse_hex_digit(literal, position + 2)?;
+                    let low = parse_hex_digit(literal, position + 3)?;
+                    bytes.push(high << 4 | low);
+                    position += 4;
                 }
+                Some(b'u') => {
+                    let (char, len) = parse_unicode_escape(literal, position)?;
+                    bytes.extend_from_slice(char.encode_utf8(&mut [0; 4]).as_bytes());
+                    position += len;
+                }
+                Some(_) => return Err(error(position, DecodeErrorKind::InvalidEscape)),
+                None => return Err(error(position + 1, DecodeErrorKind::Unterminated)),
+            },
+            _ => {
+                bytes.push(byte);
+                position += 1;
             }
-            _ => (),
         }
+    }
 
-        // One character has been added to the string.
-        length += 1;
+    // Nothing can come after the closing quote.
+    if position + 1 < literal.len() {
+        return Err(error(position + 1, DecodeErrorKind::TrailingCharacters));
     }
+
+    Ok(bytes)
 }
 
-/// Returns the escaped length of a string.
-fn escaped_len(string: &str) -> usize {
+/// Parses the hexadecimal digit at a position in a literal. This function
+/// returns a [`DecodeError`] if there is no hexadecimal digit at the position.
+fn parse_hex_digit(literal: &[u8], position: usize) -> Result<u8, DecodeError> {
+    let error = |kind| DecodeError { position, kind };
+    let byte = *literal
+        .get(position)
+        .ok_or(error(DecodeErrorKind::Unterminated))?;
+
+    let digit = char::from(byte)
+        .to_digit(16)
+        .ok_or(error(DecodeErrorKind::InvalidHexDigit))?;
+
+    Ok(digit
+        .try_into()
+        .expect("hexadecimal digit should be less than 16"))
+}
+
+/// Parses the Unicode escape starting at a position in a literal, and returns
+/// its character and length. This function returns a [`DecodeError`] if the
+/// escape is not 1 to 6 hexadecimal digits in braces, or is not a valid
+/// character.
+fn parse_unicode_escape(literal: &[u8], position: usize) -> Result<(char, usize), DecodeError> {
+    let error = |kind| DecodeError { position, kind };
+    let rest = &literal[(position + 2)..];
+
+    let unterminated = DecodeError {
+        position: literal.len(),
+        kind: DecodeErrorKind::Unterminated,
+    };
+
+    match rest.first() {
+        Some(b'{') => (),
+        Some(_) => return Err(error(DecodeErrorKind::InvalidUnicodeEscape)),
+        None => return Err(unterminated),
+    }
+
+    let digits_len = rest.iter().position(|&b| b == b'}').ok_or(unterminated)?;
+
+    let digits = &rest[1..digits_len];
+
+    if digits.is_empty() || digits.len() > 6 {
+        return Err(error(DecodeErrorKind::InvalidUnicodeEscape));
+    }
+
+    let mut code_point = 0;
+
+    for index in 0..digits.len() {
+        let digit = parse_hex_digit(literal, position + 3 + index)?;
+        code_point = code_point << 4 | u32::from(digit);
+    }
+
+    let char = char::from_u32(code_point).ok_or(error(DecodeErrorKind::InvalidUnicodeEscape))?;
+
+    // The backslash, 'u', and braces are also part of the escape.
+    Ok((char, digits_len + 3))
+}
+
+/// Encodes bytes into a quoted string literal. Quotes and backslashes are
+/// escaped with a backslash, bytes which are ASCII control characters or not
+/// valid UTF-8 are escaped with hexadecimal digits, and other control
+/// characters are escaped with Unicode escapes.
+fn encode(bytes: &[u8]) -> String {
     // 2 characters are added for the surrounding quotes.
-    let mut len = 2;
+    let mut literal = String::with_capacity(bytes.len() + 2);
+    literal.push('"');
 
-    for char in string.chars() {
-        // Quotes and backslashes need a backslash before them, other characters
-        // are kept as-is.
-        len += match char {
-            '"' | '\\' => 2,
-            _ => 1,
+    for chunk in bytes.utf8_chunks() {
+        for char in chunk.valid().chars() {
+            match char {
+                '"' | '\\' => {
+                    literal.push('\\');
+                    literal.push(char);
+                }
+                ' '..='~' => literal.push(char),
+                '\0'..='\x7f' => {
+                    let _ = write!(literal, "\\x{:02x}", u32::from(char));
+                }
+                _ if char.is_control() => {
+                    let _ = write!(literal, "\\u{{{:x}}}", u32::from(char));
+                }
+                _ => literal.push(char),
+            }
+        }
+
+        for byte in chunk.invalid() {
+            let _ = write!(literal, "\\x{byte:02x}");
+        }
+    }
+
+    literal.push('"');
+    literal
+}
+
+/// An error caused by a string literal which could not be decoded.
+#[derive(Debug, PartialEq, Eq)]
+struct DecodeError {
+    /// The byte position of the error in the literal.
+    position: usize,
+
+    /// The [`DecodeErrorKind`].
+    kind: DecodeErrorKind,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            DecodeErrorKind::MissingOpeningQuote => "missing opening quote",
+            DecodeErrorKind::Unterminated => "unterminated literal",
+            DecodeErrorKind::InvalidEscape => "invalid escape",
+            DecodeErrorKind::InvalidHexDigit => "invalid hexadecimal digit",
+            DecodeErrorKind::InvalidUnicodeEscape => "invalid unicode escape",
+            DecodeErrorKind::TrailingCharacters => "characters after closing quote",
         };
+
+        write!(f, "{message} at position {}", self.position)
     }
+}
 
-    len
+/// A kind of [`DecodeError`].
+#[derive(Debug, PartialEq, Eq)]
+enum DecodeErrorKind {
+    /// The literal does not start with a quote.
+    MissingOpeningQuote,
+
+    /// The literal ends before its closing quote.
+    Unterminated,
+
+    /// A backslash is followed by a character which can't be escaped.
+    InvalidEscape,
+
+    /// A hexadecimal escape contains a character which is not a hexadecimal
+    /// digit.
+    InvalidHexDigit,
+
+    /// A Unicode escape is not in braces, has the wrong number of digits, or
+    /// is not a valid character.
+    InvalidUnicodeEscape,
+
+    /// There are characters after the closing quote.
+    TrailingCharacters,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::random::Random;
 
     /// Tests part one.
     #[test]
     fn part_one_works() {
-        check_unescaped_len("\"\"", 2, 0);
-        check_unescaped_len("\"abc\"", 5, 3);
-        check_unescaped_len("\"aaa\\\"aaa\"", 10, 7);
-        check_unescaped_len("\"\\x27\"", 6, 1);
+        check_decoded_len("\"\"", 2, 0);
+        check_decoded_len("\"abc\"", 5, 3);
+        check_decoded_len("\"aaa\\\"aaa\"", 10, 7);
+        check_decoded_len("\"\\x27\"", 6, 1);
     }
 
     /// Tests part two.
     #[test]
     fn part_two_works() {
-        check_escaped_len("\"\"", 2, 6);
-        check_escaped_len("\"abc\"", 5, 9);
-        check_escaped_len("\"aaa\\\"aaa\"", 10, 16);
-        check_escaped_len("\"\\x27\"", 6, 11);
+        check_encoded_len("\"\"", 2, 6);
+        check_encoded_len("\"abc\"", 5, 9);
+        check_encoded_len("\"aaa\\\"aaa\"", 10, 16);
+        check_encoded_len("\"\\x27\"", 6, 11);
+    }
+
+    /// Tests decoding and encoding strings.
+    #[test]
+    fn codec_works() {
+        assert_eq!(
+            decode("\"a\\\\b\\\"c\\x41\\xfF\""),
+            Ok(b"a\\b\"cA\xff".to_vec())
+        );
+        assert_eq!(encode(b"a\\b\"cA\xff\n"), "\"a\\\\b\\\"cA\\xff\\x0a\"");
+    }
+
+    /// Tests that decoding and encoding strings round-trips.
+    #[test]
+    fn codec_round_trips() {
+        let all_bytes: Vec<u8> = (0..=u8::MAX).collect();
+        assert_eq!(decode(&encode(&all_bytes)), Ok(all_bytes));
+
+        let mut random = Random::new();
+
+        for len in 0..200 {
+            let bytes: Vec<u8> = (0..len).map(|_| random.next_byte()).collect();
+
+            assert_eq!(decode(&encode(&bytes)), Ok(bytes));
+        }
+
+        for literal in ["\"\"", "\"abc\"", "\"aaa\\\"aaa\"", "\"\\x27\""] {
+            let bytes = decode(literal).expect("literal should be valid");
+            assert_eq!(decode(&encode(&bytes)), Ok(bytes));
+        }
+    }
+
+    /// Tests that invalid literals are decode errors.
+    #[test]
+    fn invalid_literals_are_errors() {
+        for (literal, position, kind) in [
+            ("abc\"", 0, DecodeErrorKind::MissingOpeningQuote),
+            ("", 0, DecodeErrorKind::MissingOpeningQuote),
+            ("\"abc", 4, DecodeErrorKind::Unterminated),
+            ("\"abc\\", 5, DecodeErrorKind::Unterminated),
+            ("\"\\x4", 4, DecodeErrorKind::Unterminated),
+            ("\"ab\\n\"", 3, DecodeErrorKind::InvalidEscape),
+            ("\"\\x4g\"", 4, DecodeErrorKind::InvalidHexDigit),
+            ("\"\\xz1\"", 3, DecodeErrorKind::InvalidHexDigit),
+            ("\"abc\"d", 5, DecodeErrorKind::TrailingCharacters),
+            ("\"\\u", 3, DecodeErrorKind::Unterminated),
+            ("\"\\u{41", 6, DecodeErrorKind::Unterminated),
+            ("\"\\u41\"", 1, DecodeErrorKind::InvalidUnicodeEscape),
+            ("\"\\u{}\"", 1, DecodeErrorKind::InvalidUnicodeEscape),
+            ("\"\\u{1234567}\"", 1, DecodeErrorKind::InvalidUnicodeEscape),
+            ("\"\\u{d800}\"", 1, DecodeErrorKind::InvalidUnicodeEscape),
+            ("\"\\u{4g}\"", 5, DecodeErrorKind::InvalidHexDigit),
+        ] {
+            assert_eq!(decode(literal), Err(DecodeError { position, kind }));
+        }
+
+        let error = DecodeError {
+            position: 3,
+            kind: DecodeErrorKind::InvalidEscape,
+        };
+
+        assert_eq!(error.to_string(), "invalid escape at position 3");
+        assert_eq!(part_one("\"\\x4g\""), Solution::ParseError);
+    }
+
+    /// Tests Unicode escapes.
+    #[test]
+    fn unicode_escapes_work() {
+        assert_eq!(
+            decode("\"\\u{e9}\\u{1F600}\""),
+            Ok("é😀".as_bytes().to_vec())
+        );
+        assert_eq!(encode("é\u{85}\t".as_bytes()), "\"é\\u{85}\\x09\"");
+
+        let mut random = Random::new();
+
+        for len in 0..200 {
+            let string: String = (0..len)
+                .filter_map(|_| char::from_u32(random.next_u32() % 0x11_0000))
+                .collect();
+
+            assert_eq!(decode(&encode(string.as_bytes())), Ok(string.into_bytes()));
+        }
+    }
+
+    /// Tests counting lengths in different units.
+    #[test]
+    fn length_modes_work() {
+        let combining = "\"e\\u{301}\"";
+        check_decoded_len_difference(combining, LengthMode::Bytes, 7);
+        check_decoded_len_difference(combining, LengthMode::Chars, 8);
+        check_decoded_len_difference(combining, LengthMode::ApproxGraphemes, 9);
+
+        let virama = "\"\\u{915}\\u{94d}\"";
+        check_decoded_len_difference(virama, LengthMode::Bytes, 10);
+        check_decoded_len_difference(virama, LengthMode::ApproxGraphemes, 15);
+
+        // Non-ASCII characters are counted in the same unit before and after
+        // decoding or encoding.
+        for mode in [
+            LengthMode::Bytes,
+            LengthMode::Chars,
+            LengthMode::ApproxGraphemes,
+        ] {
+            check_decoded_len_difference("\"é\"", mode, 2);
+            let solution = encoded_len_difference("\"é\"", mode);
+            assert_eq!(solution, Solution::from(4));
+        }
+
+        // Decoded strings which are not UTF-8 only have a length in bytes.
+        check_decoded_len_difference("\"\\xff\"", LengthMode::Bytes, 5);
+        let solution = decoded_len_difference("\"\\xff\"", LengthMode::Chars);
+        assert_eq!(solution, Solution::SolveError);
+
+        for (string, len) in [
+            ("e\u{301}", 1),
+            ("🇬🇧🇫🇷", 2),
+            ("🇬🇧🇫", 2),
+            ("👍🏽!", 2),
+            ("👨\u{200d}👩\u{200d}👧", 1),
+            ("a\r\nb", 3),
+            ("\n\r", 2),
+            ("\u{915}\u{94d}", 1),
+            ("\u{915}\u{93f}\u{902}", 1),
+            ("\u{939}\u{93f}\u{902}\u{926}\u{940}", 2),
+            ("\u{1100}\u{1161}\u{11a8}", 1),
+            ("\u{d55c}\u{ad6d}", 2),
+            ("\u{d55c}\u{11ab}", 1),
+            ("\u{ac00}\u{11a8}", 1),
+            ("\u{11a8}\u{1100}", 2),
+        ] {
+            assert_eq!(approx_grapheme_count(string), len, "{string:?}");
+        }
+    }
+
+    /// Checks that a string has an expected length difference after decoding
+    /// it with a [`LengthMode`].
+    fn check_decoded_len_difference(string: &str, mode: LengthMode, difference: usize) {
+        assert_eq!(
+            decoded_len_difference(string, mode),
+            Solution::from(difference)
+        );
     }
 
-    /// Checks that a string has an expected length after unescaping it.
-    fn check_unescaped_len(string: &str, before: usize, after: usize) {
+    /// Checks that a string has an expected length after decoding it.
+    fn check_decoded_len(string: &str, before: usize, after: usize) {
         assert_eq!(string.len(), before);
-        let unescaped_len = unescaped_len(string).expect("string should be terminated");
-        assert_eq!(unescaped_len, after);
+        let bytes = decode(string).expect("string should be valid");
+        assert_eq!(bytes.len(), after);
     }
 
-    /// Checks that a string has an expected length after escaping it.
-    fn check_escaped_len(string: &str, before: usize, after: usize) {
+    /// Checks that a string has an expected length after encoding it.
+    fn check_encoded_len(string: &str, before: usize, after: usize) {
         assert_eq!(string.len(), before);
-        assert_eq!(escaped_len(string), after);
+        assert_eq!(encode(string.as_bytes()).len(), after);
     }
 }
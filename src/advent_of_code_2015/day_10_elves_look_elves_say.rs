@@ -2,69 +2,376 @@
 //!
 //! [link]: https://adventofcode.com/2015/day/10
 
-use std::fmt::Write as _;
+use std::{collections::HashMap, mem};
 
 use crate::Solution;
 
 /// Solves part one.
 pub fn part_one(input: &str) -> Solution {
     // Play look-and-say on the input string 40 times.
-    let mut input = input.to_owned();
+    solve_part(input, 40)
+}
+
+/// Solves part two.
+pub fn part_two(input: &str) -> Solution {
+    // Play look-and-say on the input string 50 times.
+    solve_part(input, 50)
+}
+
+/// Solves a part by playing look-and-say on the input a number of times.
+fn solve_part(input: &str, iterations: usize) -> Solution {
+    let Some(digits) = parse_digits(input) else {
+        return Solution::ParseError;
+    };
+
+    // The digits get too long to build quickly, so only the number of each
+    // element in them is tracked.
+    let Some(len) = Elements::default().len_after(&digits, iterations) else {
+        return Solution::SolveError;
+    };
 
-    for _ in 0..40 {
-        input = look_and_say(&input);
+    len.into()
+}
+
+/// Returns the digits after playing look-and-say on them a number of times.
+fn play(digits: &[u8], iterations: usize) -> Vec<u8> {
+    let mut input = digits.to_vec();
+    let mut output = Vec::new();
+
+    for _ in 0..iterations {
+        look_and_say(&input, &mut output);
+        mem::swap(&mut input, &mut output);
     }
 
-    input.len().into()
+    input
 }
 
-/// Solves part two.
-pub fn part_two(input: &str) -> Solution {
-    // Play look-and-say on the input string 50 times. This is a little slow and
-    // there might be a better way to do this.
-    let mut input = input.to_owned();
+/// Replaces the output digits with the look-and-say digits of the input.
+fn look_and_say(input: &[u8], output: &mut Vec<u8>) {
+    output.clear();
+
+    for run in input.chunk_by(|a, b| a == b) {
+        push_count(run.len(), output);
+        output.push(run[0]);
+    }
+}
+
+/// Pushes the decimal digits of a count to some digits.
+fn push_count(mut count: usize, digits: &mut Vec<u8>) {
+    let start = digits.len();
+
+    loop {
+        let digit = count % 10;
+        digits.push(digit.try_into().expect("digit should be less than 10"));
+        count /= 10;
+
+        if count == 0 {
+            break;
+        }
+    }
+
+    // The digits were pushed from least to most significant.
+    digits[start..].reverse();
+}
+
+/// Parses digits from 0 to 9 from a string. This function returns [`None`] if
+/// the string is empty or contains any other characters.
+fn parse_digits(string: &str) -> Option<Vec<u8>> {
+    if string.is_empty() {
+        return None;
+    }
+
+    string
+        .chars()
+        .map(|char| {
+            Some(
+                char.to_digit(10)?
+                    .try_into()
+                    .expect("digit should be less than 10"),
+            )
+        })
+        .collect()
+}
+
+/// A set of elements, which are digits that never interact with their
+/// neighbours when playing look-and-say. Any digits from 1 to 3 eventually
+/// decay into Conway's 92 common elements, so the length of the digits can be
+/// found by counting their elements instead of building them.
+#[derive(Default)]
+struct Elements {
+    /// The index of each element's digits.
+    ids: HashMap<Vec<u8>, usize>,
+
+    /// The digits of each element.
+    digits: Vec<Vec<u8>>,
+
+    /// The elements that each element decays into after playing look-and-say,
+    /// or [`None`] if they have not been found yet.
+    decays: Vec<Option<Vec<usize>>>,
+}
+
+impl Elements {
+    /// Returns the length of some digits after playing look-and-say on them a
+    /// number of times. This function returns [`None`] if the length
+    /// overflows.
+    fn len_after(&mut self, digits: &[u8], iterations: usize) -> Option<u128> {
+        let mut counts: Vec<u128> = vec![0; self.digits.len()];
+
+        for id in self.split(digits) {
+            counts.resize(self.digits.len(), 0);
+            counts[id] += 1;
+        }
+
+        for _ in 0..iterations {
+            let mut next_counts = Vec::new();
+
+            for (id, count) in counts.into_iter().enumerate() {
+                if count == 0 {
+                    continue;
+                }
+
+                self.find_decay(id);
+                next_counts.resize(self.digits.len(), 0);
+                let decay = self.decays[id].as_deref().expect("decay should be found");
+
+                for &decay_id in decay {
+                    next_counts[decay_id] = count.checked_add(next_counts[decay_id])?;
+                }
+            }
+
+            counts = next_counts;
+        }
+
+        let mut len: u128 = 0;
+
+        for (id, count) in counts.into_iter().enumerate() {
+            let element_len = self.digits[id].len().try_into().ok()?;
+            len = len.checked_add(count.checked_mul(element_len)?)?;
+        }
+
+        Some(len)
+    }
+
+    /// Finds the elements that an element decays into if they have not been
+    /// found yet.
+    fn find_decay(&mut self, id: usize) {
+        if self.decays[id].is_none() {
+            let decay = self.split(&play(&self.digits[id], 1));
+            self.decays[id] = Some(decay);
+        }
+    }
+
+    /// Splits digits into elements and returns their indices.
+    fn split(&mut self, digits: &[u8]) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let mut start = 0;
+
+        for index in 1..digits.len() {
+            let left_digit = digits[index - 1];
 
-    for _ in 0..50 {
-        input = look_and_say(&input);
+            if left_digit != digits[index] && is_split(left_digit, &digits[index..]) {
+                ids.push(self.intern(&digits[start..index]));
+                start = index;
+            }
+        }
+
+        if !digits.is_empty() {
+            ids.push(self.intern(&digits[start..]));
+        }
+
+        ids
     }
 
-    input.len().into()
+    /// Returns the index of an element's digits, adding the element if it is
+    /// new.
+    fn intern(&mut self, digits: &[u8]) -> usize {
+        if let Some(&id) = self.ids.get(digits) {
+            return id;
+        }
+
+        let id = self.digits.len();
+        self.ids.insert(digits.to_vec(), id);
+        self.digits.push(digits.to_vec());
+        self.decays.push(None);
+        id
+    }
 }
 
-/// Returns a new look-and-say string from an input string.
-fn look_and_say(input: &str) -> String {
-    let mut chars = input.chars();
-    let mut current_char = chars
-        .next()
-        .expect("input string should be at least one character long");
-
-    let mut count = 1;
-    let mut output = String::new();
-
-    for char in chars {
-        if char == current_char {
-            count += 1;
-        } else {
-            let _ = write!(output, "{count}{current_char}");
-            current_char = char;
-            count = 1;
+/// Returns `true` if digits which end with a digit never interact with the
+/// digits to their right when playing look-and-say. The last digit never
+/// changes, so they only interact if it is ever the first of the right digits.
+fn is_split(left_digit: u8, right: &[u8]) -> bool {
+    // Only the start of the right digits needs to be played. These limits are
+    // not proven to show whether all digits split, so `splits_work` checks
+    // them against playing digits directly for more iterations.
+    const PREFIX_LEN: usize = 64;
+    const ITERATIONS: usize = 32;
+
+    let mut prefix = right[..right.len().min(PREFIX_LEN)].to_vec();
+    let mut next_prefix = Vec::new();
+    let mut is_complete = prefix.len() == right.len();
+
+    for _ in 0..ITERATIONS {
+        if prefix.first() == Some(&left_digit) {
+            return false;
+        }
+
+        // The last run of an incomplete prefix may be longer in the full
+        // digits, so it can't be counted.
+        if !is_complete {
+            let last_digit = *prefix.last().expect("prefix should not be empty");
+
+            while prefix.last() == Some(&last_digit) {
+                prefix.pop();
+            }
+
+            // Assume the digits interact if the prefix is too short to tell.
+            if prefix.is_empty() {
+                return false;
+            }
+        }
+
+        look_and_say(&prefix, &mut next_prefix);
+        mem::swap(&mut prefix, &mut next_prefix);
+
+        if prefix.len() > PREFIX_LEN {
+            prefix.truncate(PREFIX_LEN);
+            is_complete = false;
         }
     }
 
-    format!("{output}{count}{current_char}")
+    true
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::random::Random;
 
     /// Tests part one.
     #[test]
     fn part_one_works() {
-        assert_eq!(look_and_say("1"), "11");
-        assert_eq!(look_and_say("11"), "21");
-        assert_eq!(look_and_say("21"), "1211");
-        assert_eq!(look_and_say("1211"), "111221");
-        assert_eq!(look_and_say("111221"), "312211");
+        check_look_and_say("1", "11");
+        check_look_and_say("11", "21");
+        check_look_and_say("21", "1211");
+        check_look_and_say("1211", "111221");
+        check_look_and_say("111221", "312211");
+        check_look_and_say("0000000000001", "12011");
+
+        assert_eq!(part_one(""), Solution::ParseError);
+        assert_eq!(part_one("12a"), Solution::ParseError);
+    }
+
+    /// Tests part two.
+    #[test]
+    fn part_two_works() {
+        assert_eq!(part_one("1113122113"), Solution::from(360_154));
+        assert_eq!(part_two("1113122113"), Solution::from(5_103_798));
+        assert_eq!(solve_part("1", 5), Solution::from(6));
+    }
+
+    /// Tests that counting elements matches playing look-and-say on digits.
+    #[test]
+    fn elements_work() {
+        let mut elements = Elements::default();
+
+        let mut random = Random::new();
+
+        for len in 1..=40 {
+            let digits: Vec<u8> = (0..len).map(|_| 1 + random.next_byte() % 3).collect();
+            let mut played = digits.clone();
+            let mut next_played = Vec::new();
+
+            for iterations in 0..20 {
+                let len = elements.len_after(&digits, iterations);
+                assert_eq!(len, played.len().try_into().ok(), "{digits:?} {iterations}");
+                look_and_say(&played, &mut next_played);
+                mem::swap(&mut played, &mut next_played);
+            }
+        }
+
+        assert_eq!(Elements::default().len_after(&[], 10), Some(0));
+        assert_eq!(Elements::default().len_after(&[4, 4, 4, 4], 3), Some(4));
+    }
+
+    /// Tests that digits are only split where playing look-and-say on them
+    /// directly never makes them interact, for more iterations than
+    /// [`is_split`] plays.
+    #[test]
+    fn splits_work() {
+        let mut random = Random::new();
+
+        for _ in 0..30 {
+            let right = random_digits(&mut random);
+
+            // The last digit of the left digits never changes, so the digits
+            // only interact if it is ever the first of the right digits.
+            let mut first_digits = [false; 10];
+            let mut played = right.clone();
+            let mut next_played = Vec::new();
+
+            for _ in 0..36 {
+                first_digits[usize::from(played[0])] = true;
+                look_and_say(&played, &mut next_played);
+                mem::swap(&mut played, &mut next_played);
+            }
+
+            for left_digit in 0..10 {
+                // Digits are only split between different digits.
+                if left_digit != right[0] {
+                    let is_split_directly = !first_digits[usize::from(left_digit)];
+                    let is_split = is_split(left_digit, &right);
+                    assert_eq!(is_split, is_split_directly, "{left_digit} {right:?}");
+                }
+            }
+        }
+    }
+
+    /// Tests that digits from 1 to 3 decay into Conway's 92 elements.
+    #[test]
+    fn conways_elements_work() {
+        for input in ["3", "1113122113"] {
+            let digits = parse_digits(input).expect("digits should be valid");
+            let mut elements = Elements::default();
+            elements
+                .len_after(&digits, 100)
+                .expect("length should not overflow");
+
+            // Find every element that can be decayed into.
+            let mut id = 0;
+
+            while id < elements.digits.len() {
+                elements.find_decay(id);
+                id += 1;
+            }
+
+            assert_eq!(elements.digits.len(), 92);
+            assert!(elements.ids.contains_key([2, 2].as_slice()));
+        }
+
+        let mut elements = Elements::default();
+        assert!(elements.len_after(&[1], 300).is_some());
+        assert!(elements.len_after(&[1], 400).is_none());
+    }
+
+    /// Returns from 1 to 4 pseudo-random digits. Most of the digits are from 1
+    /// to 3, like the digits that look-and-say produces.
+    fn random_digits(random: &mut Random) -> Vec<u8> {
+        let len = 1 + random.next_byte() % 4;
+
+        (0..len)
+            .map(|_| match random.next_byte() % 8 {
+                0 => random.next_byte() % 10,
+                n => 1 + n % 3,
+            })
+            .collect()
+    }
+
+    /// Checks that playing look-and-say once on some digits produces expected
+    /// digits.
+    fn check_look_and_say(input: &str, output: &str) {
+        let input = parse_digits(input).expect("input should be valid");
+        let output = parse_digits(output).expect("output should be valid");
+        assert_eq!(play(&input, 1), output);
     }
 }
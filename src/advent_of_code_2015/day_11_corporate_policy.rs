@@ -2,7 +2,10 @@
 //!
 //! [link]: https://adventofcode.com/2015/day/11
 
-use std::fmt::{self, Display, Formatter};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
 
 use crate::Solution;
 
@@ -15,151 +18,493 @@ pub fn part_one(input: &str) -> Solution {
     // * Must contain at lest two different pairs of letters
     //   (e.g. "aa" and "bb")
     // The next password that meets these rules must be found.
-    let Some(password) = parse_password(input) else {
+    let policy = Policy::SANTA;
+
+    let Ok(password) = parse_password(input, &policy) else {
         return Solution::ParseError;
     };
 
-    password.next_valid().to_string().into()
+    let Some(password) = password.valid_passwords(&policy).next() else {
+        return Solution::SolveError;
+    };
+
+    password.to_string().into()
 }
 
 /// Solves part two.
 pub fn part_two(input: &str) -> Solution {
     // Santa's password expired again, now we need to find the next valid one.
-    let Some(password) = parse_password(input) else {
+    let policy = Policy::SANTA;
+
+    let Ok(password) = parse_password(input, &policy) else {
         return Solution::ParseError;
     };
 
-    password.next_valid().next_valid().to_string().into()
+    let Some(password) = password.valid_passwords(&policy).nth(1) else {
+        return Solution::SolveError;
+    };
+
+    password.to_string().into()
 }
 
-/// Returns `true` if a letter is not allowed in a `Password`.
-fn is_letter_invalid(letter: u8) -> bool {
-    const LETTER_I: u8 = b'i' - b'a';
-    const LETTER_L: u8 = b'l' - b'a';
-    const LETTER_O: u8 = b'o' - b'a';
-    matches!(letter, LETTER_I | LETTER_L | LETTER_O)
+/// The rules that a [`Password`] must follow.
+struct Policy {
+    /// The number of letters in a [`Password`].
+    len: usize,
+
+    /// The letters which are not allowed, with a bit for each letter where
+    /// `'a'` is the lowest bit.
+    forbidden_letters: u32,
+
+    /// The length of the straight of increasing letters which is required.
+    straight_len: usize,
+
+    /// The number of different pairs of letters which are required.
+    pair_count: u32,
 }
 
-/// An eight-letter password.
-#[derive(Clone, Copy, PartialEq, Eq)]
+impl Policy {
+    /// The `Policy` of the new Security-Elf.
+    const SANTA: Self = Self {
+        len: 8,
+        forbidden_letters: 1 << (b'i' - b'a') | 1 << (b'l' - b'a') | 1 << (b'o' - b'a'),
+        straight_len: 3,
+        pair_count: 2,
+    };
+
+    /// Creates a new `Policy` from its password length, forbidden letters,
+    /// straight length, and pair count. This function returns [`None`] if a
+    /// forbidden letter is not a lowercase ASCII letter.
+    #[allow(
+        dead_code,
+        reason = "only the Security-Elf's policy is used to solve puzzles"
+    )]
+    fn new(
+        len: usize,
+        forbidden_letters: &str,
+        straight_len: usize,
+        pair_count: u32,
+    ) -> Option<Self> {
+        let mut letter_bits = 0;
+
+        for letter in forbidden_letters.bytes() {
+            if !letter.is_ascii_lowercase() {
+                return None;
+            }
+
+            letter_bits |= 1 << (letter - b'a');
+        }
+
+        Some(Self {
+            len,
+            forbidden_letters: letter_bits,
+            straight_len,
+            pair_count,
+        })
+    }
+
+    /// Returns `true` if a letter is not allowed in a [`Password`].
+    fn is_letter_forbidden(&self, letter: u8) -> bool {
+        self.forbidden_letters & 1 << letter != 0
+    }
+
+    /// Returns an iterator over the letters which are allowed in a
+    /// [`Password`].
+    fn allowed_letters(&self) -> impl Iterator<Item = u8> {
+        (0..26).filter(|&l| !self.is_letter_forbidden(l))
+    }
+}
+
+/// A password.
+#[derive(Clone, PartialEq, Eq)]
 struct Password {
-    /// The letters of the `Password` where `'a'` is `0` and `'z'` is `25`.
-    letters: [u8; 8],
+    /// The letters of the `Password` in reverse order, where `'a'` is `0` and
+    /// `'z'` is `25`.
+    letters: Vec<u8>,
 }
 
 impl Password {
-    /// Returns the next valid `Password`.
-    fn next_valid(mut self) -> Self {
+    /// Returns the first valid `Password` for a [`Policy`]. This function
+    /// returns [`None`] if no `Password` can follow the [`Policy`].
+    #[cfg(test)]
+    fn first_valid(policy: &Policy) -> Option<Self> {
+        let mut password = Self {
+            letters: vec![0; policy.len],
+        };
+
+        password.complete(policy.len, policy).then_some(password)
+    }
+
+    /// Returns the next valid `Password` for a [`Policy`] by checking every
+    /// `Password` after this one. This function returns [`None`] if there are
+    /// no valid `Password`s after this one.
+    #[cfg(test)]
+    fn next_valid(mut self, policy: &Policy) -> Option<Self> {
         loop {
-            self.increment();
-            self.clean();
+            if !self.increment() || !self.clean(policy) {
+                return None;
+            }
 
-            if self.is_valid() {
-                break self;
+            if self.is_valid(policy) {
+                break Some(self);
             }
         }
     }
 
-    /// Increments the `Password`.
-    fn increment(&mut self) {
-        let mut index = 0;
+    /// Returns the next valid `Password` for a [`Policy`] by keeping as many
+    /// of the first letters as possible, then building the smallest valid
+    /// letters after them. This function returns [`None`] if there are no
+    /// valid `Password`s after this one.
+    fn next_valid_direct(&self, policy: &Policy) -> Option<Self> {
+        // The letters are in reverse order in memory, so the last letter is
+        // changed first.
+        for index in 0..self.letters.len() {
+            let prefix = &self.letters[(index + 1)..];
+
+            if prefix.iter().any(|&l| policy.is_letter_forbidden(l)) {
+                continue;
+            }
 
-        loop {
-            // Carry to the next letter if it is "z".
-            if self.letters[index] == 25 {
-                self.letters[index] = 0;
-                index = (index + 1) % 8;
-            } else {
-                self.letters[index] += 1;
-                break;
+            for letter in policy.allowed_letters() {
+                if letter <= self.letters[index] {
+                    continue;
+                }
+
+                let mut password = self.clone();
+                password.letters[index] = letter;
+
+                if password.complete(index, policy) {
+                    return Some(password);
+                }
             }
         }
+
+        None
     }
 
-    /// Cleans the `Password` by incrementing past invalid letters.
-    /// (e.g. `"hijklmno"` becomes `"hjaaaaaa"`).
-    fn clean(&mut self) {
-        let mut is_cleaning = false;
+    /// Returns an iterator over the valid `Password`s after this one for a
+    /// [`Policy`], which ends at the last valid `Password`.
+    fn valid_passwords(self, policy: &Policy) -> ValidPasswords<'_> {
+        ValidPasswords {
+            password: self,
+            policy,
+        }
+    }
 
-        for index in (0..8).rev() {
-            if is_cleaning {
-                self.letters[index] = 0;
-            } else if is_letter_invalid(self.letters[index]) {
-                self.letters[index] += 1;
-                is_cleaning = true;
+    /// Returns the number of valid `Password`s before this one for a
+    /// [`Policy`]. Instead of checking each `Password`, the `Password`s are
+    /// grouped by the letters that they share with this one, and the ways to
+    /// fill in the rest of each group are counted.
+    fn count_valid_before(&self, policy: &Policy) -> u128 {
+        let mut count = 0;
+        let mut progress = Progress::default();
+        let mut valid_counts = HashMap::new();
+
+        // The letters are in reverse order in memory, so the first letter is
+        // the last one in memory.
+        for (len, &bound_letter) in self.letters.iter().enumerate().rev() {
+            for letter in policy.allowed_letters() {
+                if letter >= bound_letter {
+                    break;
+                }
+
+                let progress = progress.push(letter, policy);
+                count += count_valid_fills(len, progress, policy, &mut valid_counts);
             }
+
+            // Every other `Password` in the group has a forbidden letter.
+            if policy.is_letter_forbidden(bound_letter) {
+                break;
+            }
+
+            progress = progress.push(bound_letter, policy);
         }
+
+        count
     }
 
-    /// Returns `true` if the `Password` is valid, ignoring invalid letters.
-    fn is_valid(self) -> bool {
-        self.has_straight() && self.has_pairs()
+    /// Replaces the last `len` letters with the smallest letters that make the
+    /// `Password` valid for a [`Policy`]. This function returns `false` if no
+    /// letters can make the `Password` valid.
+    fn complete(&mut self, len: usize, policy: &Policy) -> bool {
+        let mut progress = Progress::default();
+
+        for &letter in self.letters[len..].iter().rev() {
+            progress = progress.push(letter, policy);
+        }
+
+        self.fill(len, progress, policy, &mut HashSet::new())
     }
 
-    /// Returns `true` if the `Password` has a straight of three letters.
-    fn has_straight(self) -> bool {
-        // The window is declared in reverse because the password is in reverse
-        // order in memory.
-        for (c, b, a) in self.letters.windows(3).map(|w| (w[0], w[1], w[2])) {
-            if b == a + 1 && c == a + 2 {
+    /// Fills in the last `len` letters of the `Password` with the smallest
+    /// letters that make it valid for a [`Policy`] from its [`Progress`] before
+    /// them. Each length and [`Progress`] which can't be filled in is
+    /// remembered so that it is only tried once.
+    fn fill(
+        &mut self,
+        len: usize,
+        progress: Progress,
+        policy: &Policy,
+        dead_ends: &mut HashSet<(usize, Progress)>,
+    ) -> bool {
+        if len == 0 {
+            return progress.is_valid(policy);
+        }
+
+        if dead_ends.contains(&(len, progress)) {
+            return false;
+        }
+
+        for letter in policy.allowed_letters() {
+            self.letters[len - 1] = letter;
+
+            if self.fill(len - 1, progress.push(letter, policy), policy, dead_ends) {
                 return true;
             }
         }
 
+        dead_ends.insert((len, progress));
         false
     }
 
-    /// Returns `true` if the `Password` has at least two different pairs of
-    /// letters.
-    fn has_pairs(self) -> bool {
-        let mut found_pair = None;
+    /// Increments the `Password`. This function returns `false` if the
+    /// `Password` overflowed and wrapped around to all `'a'`s.
+    #[cfg(test)]
+    fn increment(&mut self) -> bool {
+        self.increment_from(0)
+    }
 
-        for (a, b) in self.letters.windows(2).map(|w| (w[0], w[1])) {
-            if a == b && found_pair.get_or_insert(a) != &a {
+    /// Increments the `Password` from a letter's index, ignoring the letters
+    /// after it. This function returns `false` if the `Password` overflowed.
+    #[cfg(test)]
+    fn increment_from(&mut self, index: usize) -> bool {
+        for letter in &mut self.letters[index..] {
+            // Carry to the next letter if it is "z".
+            if *letter == 25 {
+                *letter = 0;
+            } else {
+                *letter += 1;
                 return true;
             }
         }
 
         false
     }
+
+    /// Cleans the `Password` for a [`Policy`] by incrementing past forbidden
+    /// letters (e.g. `"hijklmno"` becomes `"hjaaaaaa"`). This function returns
+    /// `false` if the `Password` overflowed.
+    #[cfg(test)]
+    fn clean(&mut self, policy: &Policy) -> bool {
+        // The letters are in reverse order in memory, so the first forbidden
+        // letter is the last one in memory.
+        while let Some(index) = self
+            .letters
+            .iter()
+            .rposition(|&l| policy.is_letter_forbidden(l))
+        {
+            self.letters[..index].fill(0);
+
+            if !self.increment_from(index) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns `true` if the `Password` is valid for a [`Policy`], ignoring
+    /// forbidden letters.
+    #[cfg(test)]
+    fn is_valid(&self, policy: &Policy) -> bool {
+        let mut progress = Progress::default();
+
+        for &letter in self.letters.iter().rev() {
+            progress = progress.push(letter, policy);
+        }
+
+        progress.is_valid(policy)
+    }
+}
+
+/// Returns the number of ways to fill in the last `len` letters of a
+/// [`Password`] that make it valid for a [`Policy`] from its [`Progress`]
+/// before them. The count for each length and [`Progress`] is remembered so
+/// that it is only found once.
+fn count_valid_fills(
+    len: usize,
+    progress: Progress,
+    policy: &Policy,
+    valid_counts: &mut HashMap<(usize, Progress), u128>,
+) -> u128 {
+    if len == 0 {
+        return u128::from(progress.is_valid(policy));
+    }
+
+    if let Some(&count) = valid_counts.get(&(len, progress)) {
+        return count;
+    }
+
+    let count = policy
+        .allowed_letters()
+        .map(|l| count_valid_fills(len - 1, progress.push(l, policy), policy, valid_counts))
+        .sum();
+
+    valid_counts.insert((len, progress), count);
+    count
+}
+
+/// Returns the number of valid [`Password`]s for a [`Policy`] from a start
+/// [`Password`] up to, but not including, an end [`Password`].
+#[allow(dead_code, reason = "valid passwords are only counted for analysis")]
+fn count_valid(start: &Password, end: &Password, policy: &Policy) -> u128 {
+    let start_count = start.count_valid_before(policy);
+    end.count_valid_before(policy).saturating_sub(start_count)
+}
+
+/// An iterator over the valid [`Password`]s after a [`Password`] for a
+/// [`Policy`].
+struct ValidPasswords<'a> {
+    /// The last [`Password`].
+    password: Password,
+
+    /// The [`Policy`].
+    policy: &'a Policy,
+}
+
+impl Iterator for ValidPasswords<'_> {
+    type Item = Password;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.password = self.password.next_valid_direct(self.policy)?;
+        Some(self.password.clone())
+    }
 }
 
 impl Display for Password {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let mut chars = self.letters.map(|l| l + b'a');
-        chars.reverse();
+        let chars: Vec<u8> = self.letters.iter().rev().map(|l| l + b'a').collect();
         f.write_str(str::from_utf8(&chars).expect("password should be valid UTF-8"))
     }
 }
 
-/// Parses a [`Password`] from a string. This function returns [`None`] if a
-/// [`Password`] could not be parsed.
-fn parse_password(input: &str) -> Option<Password> {
-    let input = input.as_bytes();
+/// The progress of a [`Password`] towards being valid for a [`Policy`] after
+/// some of its letters.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct Progress {
+    /// The last letter.
+    last_letter: Option<u8>,
+
+    /// The length of the straight ending at the last letter, or the required
+    /// length if a straight has been found.
+    straight_len: usize,
+
+    /// The letters of the pairs which have been found, with a bit for each
+    /// letter where `'a'` is the lowest bit. Every bit is set once enough
+    /// pairs have been found.
+    pairs: u32,
+}
+
+impl Progress {
+    /// Returns the `Progress` after another letter for a [`Policy`].
+    fn push(self, letter: u8, policy: &Policy) -> Self {
+        let straight_len = match self.last_letter {
+            _ if self.straight_len >= policy.straight_len => self.straight_len,
+            Some(last_letter) if last_letter + 1 == letter => self.straight_len + 1,
+            _ => 1,
+        };
+
+        let mut pairs = self.pairs;
+
+        if self.last_letter == Some(letter) {
+            pairs |= 1 << letter;
+
+            // Forget which pairs were found, so that fewer `Progress`es are
+            // different.
+            if pairs.count_ones() >= policy.pair_count {
+                pairs = u32::MAX;
+            }
+        }
 
-    if input.len() < 8 {
-        return None;
+        Self {
+            last_letter: Some(letter),
+            straight_len,
+            pairs,
+        }
     }
 
-    let mut letters = [0; 8];
+    /// Returns `true` if the letters are valid for a [`Policy`], ignoring
+    /// forbidden letters.
+    fn is_valid(self, policy: &Policy) -> bool {
+        self.straight_len >= policy.straight_len && self.pairs.count_ones() >= policy.pair_count
+    }
+}
 
-    for index in 0..8 {
-        let letter = input[index];
+/// An error caused by a [`Password`] which could not be parsed.
+#[derive(Debug, PartialEq, Eq)]
+enum PasswordError {
+    /// The password has the wrong number of characters.
+    WrongLength {
+        /// The number of characters.
+        len: usize,
+
+        /// The number of characters required by the [`Policy`].
+        expected_len: usize,
+    },
+
+    /// The password has a character which is not a lowercase ASCII letter.
+    InvalidChar {
+        /// The character.
+        char: char,
+
+        /// The position of the character in the password.
+        position: usize,
+    },
+}
 
-        if !letter.is_ascii_lowercase() {
-            return None;
+impl Display for PasswordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongLength { len, expected_len } => {
+                write!(f, "password has {len} characters instead of {expected_len}")
+            }
+            Self::InvalidChar { char, position } => {
+                write!(f, "invalid character {char:?} at position {position}")
+            }
         }
+    }
+}
+
+/// Parses a [`Password`] for a [`Policy`] from a string. This function returns
+/// a [`PasswordError`] if the string is not a [`Password`] of the right length.
+fn parse_password(input: &str, policy: &Policy) -> Result<Password, PasswordError> {
+    let len = input.chars().count();
 
-        letters[index] = letter - b'a';
+    if len != policy.len {
+        return Err(PasswordError::WrongLength {
+            len,
+            expected_len: policy.len,
+        });
+    }
+
+    let mut letters = Vec::with_capacity(policy.len);
+
+    for (position, char) in input.chars().enumerate() {
+        if !char.is_ascii_lowercase() {
+            return Err(PasswordError::InvalidChar { char, position });
+        }
+
+        letters.push(u8::try_from(char).expect("letter should be ASCII") - b'a');
     }
 
     letters.reverse();
-    Some(Password { letters })
+    Ok(Password { letters })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::random::Random;
 
     /// Tests part one.
     #[test]
@@ -171,16 +516,247 @@ mod tests {
         assert_eq!(part_one("ghijklmn"), "ghjaabcc".into());
     }
 
+    /// Tests that finding the next valid [`Password`] directly matches brute
+    /// force.
+    #[test]
+    fn direct_search_works() {
+        let policy = Policy::SANTA;
+
+        let mut random = Random::new();
+
+        for _ in 0..2 {
+            let letters = (0..8).map(|_| random.next_byte() % 26).collect();
+
+            check_next_valid(&Password { letters }, &policy);
+        }
+
+        // Brute force is too slow for many 8-letter passwords, so most random
+        // passwords are shorter with similar rules.
+        let short_policies = [
+            Policy::new(5, "ilo", 3, 1).expect("policy should be valid"),
+            Policy::new(5, "ilo", 2, 2).expect("policy should be valid"),
+        ];
+
+        for policy in &short_policies {
+            for _ in 0..150 {
+                let letters = (0..policy.len).map(|_| random.next_byte() % 26).collect();
+                check_next_valid(&Password { letters }, policy);
+            }
+        }
+
+        for input in ["abcdefgh", "ghijklmn", "zzzzzzzz", "xxyzzzzz", "iiiiiiii"] {
+            let password = parse_password(input, &policy).expect("input should be valid");
+            check_next_valid(&password, &policy);
+        }
+    }
+
+    /// Tests custom [`Policy`]s.
+    #[test]
+    fn policies_work() {
+        let policy = Policy::new(5, "az", 4, 1).expect("policy should be valid");
+        let password = Password::first_valid(&policy).map(|p| p.to_string());
+        assert_eq!(password, Some("bbcde".to_owned()));
+
+        let policy = Policy::new(10, "", 0, 3).expect("policy should be valid");
+        let password = Password::first_valid(&policy).map(|p| p.to_string());
+        assert_eq!(password, Some("aaaaaabbcc".to_owned()));
+
+        // A straight can't be longer than the alphabet.
+        let policy = Policy::new(30, "", 27, 0).expect("policy should be valid");
+        assert!(Password::first_valid(&policy).is_none());
+
+        assert!(Policy::new(8, "iOl", 3, 2).is_none());
+
+        let mut random = Random::new();
+        let mut next_random = |range: u8| random.next_byte() % range;
+
+        for _ in 0..15 {
+            let len = usize::from(3 + next_random(3));
+
+            let forbidden_letters: String = (0..next_random(6))
+                .map(|_| char::from(b'a' + next_random(26)))
+                .collect();
+
+            let straight_len = usize::from(next_random(4));
+            let pair_count = u32::from(next_random(3));
+            let policy = Policy::new(len, &forbidden_letters, straight_len, pair_count)
+                .expect("policy should be valid");
+
+            let letters = (0..len).map(|_| next_random(26)).collect();
+            check_next_valid(&Password { letters }, &policy);
+        }
+    }
+
+    /// Tests iterating over and counting valid [`Password`]s.
+    #[test]
+    fn valid_passwords_work() {
+        let policy = Policy::SANTA;
+        let password = parse_password("abcdefgh", &policy).expect("input should be valid");
+
+        let passwords: Vec<String> = password
+            .clone()
+            .valid_passwords(&policy)
+            .take(3)
+            .map(|p| p.to_string())
+            .collect();
+
+        assert_eq!(passwords, ["abcdffaa", "abcdffbb", "abcdffcc"]);
+
+        let end = parse_password("abcdffcc", &policy).expect("input should be valid");
+        assert_eq!(count_valid(&password, &end, &policy), 2);
+        assert_eq!(count_valid(&end, &password, &policy), 0);
+
+        let start = parse_password("aaaaaaaa", &policy).expect("input should be valid");
+        let end = parse_password("zzzzzzzz", &policy).expect("input should be valid");
+        assert!(count_valid(&start, &end, &policy) > 0);
+
+        let mut random = Random::new();
+        let mut next_random = |range: u8| random.next_byte() % range;
+
+        for _ in 0..10 {
+            let forbidden_letters: String = (0..next_random(6))
+                .map(|_| char::from(b'a' + next_random(26)))
+                .collect();
+
+            let straight_len = usize::from(next_random(4));
+            let pair_count = u32::from(next_random(2));
+            let policy = Policy::new(3, &forbidden_letters, straight_len, pair_count)
+                .expect("policy should be valid");
+
+            check_valid_counts(&policy, 61);
+        }
+
+        // Multiple pairs need longer passwords.
+        let policy = Policy::new(4, "ilo", 2, 2).expect("policy should be valid");
+        check_valid_counts(&policy, 1999);
+    }
+
+    /// Tests that invalid passwords are errors.
+    #[test]
+    fn invalid_passwords_are_errors() {
+        let policy = Policy::SANTA;
+
+        for (input, error) in [
+            (
+                "abcdefg",
+                PasswordError::WrongLength {
+                    len: 7,
+                    expected_len: 8,
+                },
+            ),
+            (
+                "abcdefghi",
+                PasswordError::WrongLength {
+                    len: 9,
+                    expected_len: 8,
+                },
+            ),
+            (
+                "abcdefgh\n",
+                PasswordError::WrongLength {
+                    len: 9,
+                    expected_len: 8,
+                },
+            ),
+            (
+                "abcDefgh",
+                PasswordError::InvalidChar {
+                    char: 'D',
+                    position: 3,
+                },
+            ),
+            (
+                "abcdefgé",
+                PasswordError::InvalidChar {
+                    char: 'é',
+                    position: 7,
+                },
+            ),
+        ] {
+            assert_eq!(parse_password(input, &policy).err(), Some(error));
+        }
+
+        let error = PasswordError::WrongLength {
+            len: 9,
+            expected_len: 8,
+        };
+        assert_eq!(error.to_string(), "password has 9 characters instead of 8");
+        assert_eq!(part_one("abcdefgh\n"), Solution::ParseError);
+
+        // There are no valid passwords after the last one.
+        assert_eq!(part_one("zzzzzzzz"), Solution::SolveError);
+        assert_eq!(part_one("xxyzzaaa"), "xxyzzaab".into());
+        assert_eq!(part_two("xxyzzaaa"), "xxyzzaac".into());
+        assert_eq!(part_one("zzzzzzza"), Solution::SolveError);
+    }
+
+    /// Checks that finding the next valid [`Password`] for a [`Policy`]
+    /// directly matches brute force.
+    fn check_next_valid(password: &Password, policy: &Policy) {
+        let expected_password = password.clone().next_valid(policy);
+        let password = password.next_valid_direct(policy);
+        assert!(password == expected_password);
+    }
+
+    /// Checks that counting valid [`Password`]s for a [`Policy`] matches
+    /// checking and iterating over every `Password`. Counting the valid
+    /// `Password`s before one is slow, so it is only checked at an interval.
+    /// The interval should not share a factor with 26 so that the checked
+    /// `Password`s end in every letter.
+    fn check_valid_counts(policy: &Policy, interval: usize) {
+        // Count every valid password, ignoring passwords with forbidden
+        // letters, and check the count before some of them.
+        let start = Password {
+            letters: vec![0; policy.len],
+        };
+        let end = Password {
+            letters: vec![25; policy.len],
+        };
+
+        let mut password = start.clone();
+        let mut count = 0;
+
+        for index in 0.. {
+            if index % interval == 0 {
+                assert_eq!(password.count_valid_before(policy), count, "{password}");
+            }
+
+            let is_allowed = password
+                .letters
+                .iter()
+                .all(|&l| !policy.is_letter_forbidden(l));
+            count += u128::from(is_allowed && password.is_valid(policy));
+
+            if !password.increment() {
+                break;
+            }
+        }
+
+        // Count valid passwords by iterating over them.
+        let iterated_count = start
+            .clone()
+            .valid_passwords(policy)
+            .take_while(|p| *p != end)
+            .count();
+
+        let iterated_count = u128::try_from(iterated_count).expect("count should fit in u128")
+            + u128::from(start.is_valid(policy));
+
+        assert_eq!(count_valid(&start, &end, policy), iterated_count);
+        assert_eq!(iterated_count + u128::from(end.is_valid(policy)), count);
+    }
+
     /// Returns `true` is a [`Password`] is valid from a string.
     fn is_password_valid(input: &str) -> bool {
-        let password = parse_password(input).expect("input should be valid");
-        let mut cleaned_password = password;
-        cleaned_password.clean();
+        let policy = Policy::SANTA;
+        let password = parse_password(input, &policy).expect("input should be valid");
+        let mut cleaned_password = password.clone();
+        cleaned_password.clean(&policy);
 
         if password != cleaned_password {
             return false;
         }
 
-        password.is_valid()
+        password.is_valid(&policy)
     }
 }
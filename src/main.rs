@@ -1,3 +1,6 @@
+mod md5;
+#[cfg(test)]
+mod random;
 mod solution;
 
 pub use crate::solution::Solution;
@@ -0,0 +1,345 @@
+//! MD5 hashing, as described by [RFC 1321][link].
+//!
+//! [link]: https://www.rfc-editor.org/rfc/rfc1321
+
+/// The amount to rotate by in each step.
+const SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, //
+    5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, //
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, //
+    6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, //
+];
+
+/// The constant added in each step, from the sines of integers.
+#[rustfmt::skip]
+const SINES: [u32; 64] = [
+    0xd76a_a478, 0xe8c7_b756, 0x2420_70db, 0xc1bd_ceee,
+    0xf57c_0faf, 0x4787_c62a, 0xa830_4613, 0xfd46_9501,
+    0x6980_98d8, 0x8b44_f7af, 0xffff_5bb1, 0x895c_d7be,
+    0x6b90_1122, 0xfd98_7193, 0xa679_438e, 0x49b4_0821,
+    0xf61e_2562, 0xc040_b340, 0x265e_5a51, 0xe9b6_c7aa,
+    0xd62f_105d, 0x0244_1453, 0xd8a1_e681, 0xe7d3_fbc8,
+    0x21e1_cde6, 0xc337_07d6, 0xf4d5_0d87, 0x455a_14ed,
+    0xa9e3_e905, 0xfcef_a3f8, 0x676f_02d9, 0x8d2a_4c8a,
+    0xfffa_3942, 0x8771_f681, 0x6d9d_6122, 0xfde5_380c,
+    0xa4be_ea44, 0x4bde_cfa9, 0xf6bb_4b60, 0xbebf_bc70,
+    0x289b_7ec6, 0xeaa1_27fa, 0xd4ef_3085, 0x0488_1d05,
+    0xd9d4_d039, 0xe6db_99e5, 0x1fa2_7cf8, 0xc4ac_5665,
+    0xf429_2244, 0x432a_ff97, 0xab94_23a7, 0xfc93_a039,
+    0x655b_59c3, 0x8f0c_cc92, 0xffef_f47d, 0x8584_5dd1,
+    0x6fa8_7e4f, 0xfe2c_e6e0, 0xa301_4314, 0x4e08_11a1,
+    0xf753_7e82, 0xbd3a_f235, 0x2ad7_d2bb, 0xeb86_d391,
+];
+
+/// The length of a block in bytes.
+const BLOCK_LEN: usize = 64;
+
+/// Returns the MD5 hash of some bytes.
+pub fn compute(bytes: &[u8]) -> [u8; 16] {
+    let mut hasher = Md5::new();
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
+/// An MD5 hasher.
+#[derive(Clone)]
+pub struct Md5 {
+    /// The state after compressing every whole block.
+    state: [u32; 4],
+
+    /// The bytes which do not fill a whole block yet.
+    block: [u8; BLOCK_LEN],
+
+    /// The number of bytes in the block.
+    block_len: usize,
+
+    /// The number of bytes which have been hashed.
+    len: u64,
+}
+
+impl Md5 {
+    /// Creates a new `Md5` hasher.
+    pub fn new() -> Self {
+        Self {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            block: [0; BLOCK_LEN],
+            block_len: 0,
+            len: 0,
+        }
+    }
+
+    /// Hashes some bytes.
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let start = self.block_len;
+            let count = bytes.len().min(BLOCK_LEN - start);
+            self.block[start..start + count].copy_from_slice(&bytes[..count]);
+            self.block_len += count;
+            self.len += u64::try_from(count).expect("count should fit in u64");
+            bytes = &bytes[count..];
+
+            if self.block_len == BLOCK_LEN {
+                compress(&mut self.state, &to_words(&self.block));
+                self.block_len = 0;
+            }
+        }
+    }
+
+    /// Consumes the `Md5` hasher and returns the hash.
+    pub fn finalize(mut self) -> [u8; 16] {
+        let bit_len = self.len.wrapping_mul(8);
+        let start = self.block_len;
+
+        // Pad the message with a 1 bit and enough 0 bits for the length to end
+        // a block.
+        self.block[start] = 0x80;
+        self.block[start + 1..].fill(0);
+
+        if start + 1 > BLOCK_LEN - 8 {
+            compress(&mut self.state, &to_words(&self.block));
+            self.block.fill(0);
+        }
+
+        self.block[BLOCK_LEN - 8..].copy_from_slice(&bit_len.to_le_bytes());
+        compress(&mut self.state, &to_words(&self.block));
+        to_hash(self.state)
+    }
+}
+
+/// An MD5 hasher for messages which start with the same prefix. The prefix's
+/// whole blocks are only compressed once, and a short suffix is written into a
+/// final block which already holds the rest of the prefix and the padding, so
+/// each message only needs one block to be compressed.
+#[derive(Clone)]
+pub struct PrefixHasher {
+    /// The hasher after hashing the prefix.
+    hasher: Md5,
+
+    /// The words of the final block, padded for a suffix of length
+    /// `suffix_len`.
+    words: [u32; 16],
+
+    /// The suffix length the final block is padded for, if any.
+    suffix_len: Option<usize>,
+}
+
+impl PrefixHasher {
+    /// Creates a new `PrefixHasher` for messages which start with a prefix.
+    pub fn new(prefix: &[u8]) -> Self {
+        let mut hasher = Md5::new();
+        hasher.update(prefix);
+
+        Self {
+            hasher,
+            words: [0; 16],
+            suffix_len: None,
+        }
+    }
+
+    /// Returns the hash of the prefix followed by a suffix. Padding the final
+    /// block is only repeated when the suffix's length changes.
+    pub fn hash(&mut self, suffix: &[u8]) -> [u8; 16] {
+        let start = self.hasher.block_len;
+        let end = start + suffix.len();
+
+        // The suffix, a 1 bit, and the length do not fit in the final block,
+        // so hash the suffix with a clone of the prefix's hasher instead.
+        if end + 1 > BLOCK_LEN - 8 {
+            let mut hasher = self.hasher.clone();
+            hasher.update(suffix);
+            return hasher.finalize();
+        }
+
+        if self.suffix_len != Some(suffix.len()) {
+            let len =
+                self.hasher.len + u64::try_from(suffix.len()).expect("length should fit in u64");
+
+            let mut block = self.hasher.block;
+            block[end] = 0x80;
+            block[end + 1..BLOCK_LEN - 8].fill(0);
+            block[BLOCK_LEN - 8..].copy_from_slice(&len.wrapping_mul(8).to_le_bytes());
+            self.words = to_words(&block);
+            self.suffix_len = Some(suffix.len());
+        }
+
+        // Only the bytes of the suffix are replaced, so the rest of the final
+        // block does not need to be converted to words again.
+        for (index, &byte) in (start..end).zip(suffix) {
+            let shift = index % 4 * 8;
+            let word = &mut self.words[index / 4];
+            *word = (*word & !(0xff << shift)) | (u32::from(byte) << shift);
+        }
+
+        let mut state = self.hasher.state;
+        compress(&mut state, &self.words);
+        to_hash(state)
+    }
+}
+
+/// Converts a block into little-endian words.
+fn to_words(block: &[u8; BLOCK_LEN]) -> [u32; 16] {
+    let mut words = [0; 16];
+
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    words
+}
+
+/// Converts a state into a hash.
+fn to_hash(state: [u32; 4]) -> [u8; 16] {
+    let mut hash = [0; 16];
+
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+
+    hash
+}
+
+/// Compresses a block's words into a state.
+fn compress(state: &mut [u32; 4], words: &[u32; 16]) {
+    let [mut a, mut b, mut c, mut d] = *state;
+
+    // Every step is unrolled so that its constants are known at compile time.
+    // Each step updates a different part of the state instead of rotating it.
+    macro_rules! round {
+        ($mix:ident, |$i:ident| $index:expr, $($step:literal $($steps:literal)*),*) => {
+            round!(@steps $mix, |$i| $index, [a b c d] $($step $($steps)*)*);
+        };
+        (@steps $mix:ident, |$i:ident| $index:expr, [$a:ident $b:ident $c:ident $d:ident]) => {};
+        (@steps $mix:ident, |$i:ident| $index:expr, [$a:ident $b:ident $c:ident $d:ident]
+            $step:literal $($steps:literal)*) => {
+            let word = words[const {
+                let $i: usize = $step;
+                $index
+            }];
+
+            // The mixing function depends on the last step, so it is added
+            // last to keep the other additions off the critical path.
+            let sum = $a
+                .wrapping_add(word)
+                .wrapping_add(const { SINES[$step] })
+                .wrapping_add($mix($b, $c, $d));
+
+            $a = $b.wrapping_add(sum.rotate_left(const { SHIFTS[$step] }));
+            round!(@steps $mix, |$i| $index, [$d $a $b $c] $($steps)*);
+        };
+    }
+
+    round!(mix_f, |i| i, 0 1 2 3, 4 5 6 7, 8 9 10 11, 12 13 14 15);
+    round!(mix_g, |i| (5 * i + 1) % 16, 16 17 18 19, 20 21 22 23, 24 25 26 27, 28 29 30 31);
+    round!(mix_h, |i| (3 * i + 5) % 16, 32 33 34 35, 36 37 38 39, 40 41 42 43, 44 45 46 47);
+    round!(mix_i, |i| (7 * i) % 16, 48 49 50 51, 52 53 54 55, 56 57 58 59, 60 61 62 63);
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// The mixing function for the first round. This picks bits from `y` where
+/// `x` is set and from `z` elsewhere, like `(x & y) | (!x & z)`.
+fn mix_f(x: u32, y: u32, z: u32) -> u32 {
+    z ^ (x & (y ^ z))
+}
+
+/// The mixing function for the second round. This picks bits from `x` where
+/// `z` is set and from `y` elsewhere, like `(x & z) | (y & !z)`.
+fn mix_g(x: u32, y: u32, z: u32) -> u32 {
+    y ^ (z & (x ^ y))
+}
+
+/// The mixing function for the third round.
+fn mix_h(x: u32, y: u32, z: u32) -> u32 {
+    x ^ y ^ z
+}
+
+/// The mixing function for the fourth round.
+fn mix_i(x: u32, y: u32, z: u32) -> u32 {
+    y ^ (x | !z)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use super::*;
+
+    /// Tests the RFC 1321 test suite.
+    #[test]
+    fn rfc_1321_works() {
+        check_hash("", "d41d8cd98f00b204e9800998ecf8427e");
+        check_hash("a", "0cc175b9c0f1b6a831c399e269772661");
+        check_hash("abc", "900150983cd24fb0d6963f7d28e17f72");
+        check_hash("message digest", "f96b697d7cb7938d525a2f31aaf161d0");
+        check_hash(
+            "abcdefghijklmnopqrstuvwxyz",
+            "c3fcd3d76192e4007dfb496cca67e13b",
+        );
+
+        check_hash(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+            "d174ab98d277d9f5a5611c2c9f419d9f",
+        );
+
+        check_hash(
+            "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+            "57edf4a22be3c955ac49da2e2107b67a",
+        );
+    }
+
+    /// Tests that hashing bytes in parts matches hashing them at once.
+    #[test]
+    fn update_works() {
+        let bytes: Vec<u8> = (0..=200).collect();
+
+        for len in 0..bytes.len() {
+            let expected_hash = compute(&bytes[..len]);
+
+            for split in [0, 1, len / 2, 55, 56, 63, 64, 65, 128] {
+                let split = split.min(len);
+                let mut hasher = Md5::new();
+                hasher.update(&bytes[..split]);
+
+                // Clone the hasher to check that it can be reused.
+                let mut prefix_hasher = hasher.clone();
+                hasher.update(&bytes[split..len]);
+                assert_eq!(hasher.finalize(), expected_hash, "{len} {split}");
+                prefix_hasher.update(&bytes[split..len]);
+                assert_eq!(prefix_hasher.finalize(), expected_hash, "{len} {split}");
+            }
+        }
+
+        check_hash("abcdef609043", "000001dbbfa3a5c83a2d506429c7b00e");
+    }
+
+    /// Tests that hashing suffixes after a prefix matches hashing them at once.
+    #[test]
+    fn prefix_hasher_works() {
+        let bytes: Vec<u8> = (0..=200).collect();
+
+        for prefix_len in [0, 8, 40, 55, 56, 63, 64, 100] {
+            let mut hasher = PrefixHasher::new(&bytes[..prefix_len]);
+
+            // Shorter suffixes are hashed after longer ones to check that the
+            // block is padded correctly.
+            for suffix_len in [20, 1, 0, 8, 60, 7] {
+                let message = &bytes[..prefix_len + suffix_len];
+                let hash = hasher.hash(&message[prefix_len..]);
+                assert_eq!(hash, compute(message), "{prefix_len} {suffix_len}");
+            }
+        }
+    }
+
+    /// Checks that a message's MD5 hash matches an expected hex string.
+    fn check_hash(message: &str, expected_hash: &str) {
+        let mut hash = String::new();
+
+        for byte in compute(message.as_bytes()) {
+            let _ = write!(hash, "{byte:02x}");
+        }
+
+        assert_eq!(hash, expected_hash);
+    }
+}
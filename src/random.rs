@@ -0,0 +1,26 @@
+//! Pseudo-random numbers for generating test data.
+
+/// A linear congruential generator which always generates the same numbers.
+pub struct Random {
+    /// The generator's state.
+    state: u32,
+}
+
+impl Random {
+    /// Creates a new `Random` generator.
+    pub fn new() -> Self {
+        Self { state: 1 }
+    }
+
+    /// Returns the next pseudo-random `u32`.
+    pub fn next_u32(&mut self) -> u32 {
+        self.state = self.state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        self.state
+    }
+
+    /// Returns the next pseudo-random byte. The lowest bits of a linear
+    /// congruential generator are not very random, so a higher byte is used.
+    pub fn next_byte(&mut self) -> u8 {
+        self.next_u32().to_be_bytes()[1]
+    }
+}
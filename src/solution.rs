@@ -60,6 +60,8 @@ impl IntoSolution for i32 {}
 impl IntoSolution for u32 {}
 impl IntoSolution for i64 {}
 impl IntoSolution for u64 {}
+impl IntoSolution for i128 {}
+impl IntoSolution for u128 {}
 impl IntoSolution for isize {}
 impl IntoSolution for usize {}
 